@@ -1,5 +1,7 @@
 #import bevy_pbr::mesh_view_bind_group
 #import bevy_pbr::mesh_struct
+#import playground::globals
+#import playground::vertex
 
 struct CustomMaterial {
     color: vec4<f32>;
@@ -13,17 +15,14 @@ var<uniform> material: CustomMaterial;
 [[group(2), binding(0)]]
 var<uniform> mesh: Mesh;
 
-struct Vertex {
-    [[location(0)]] position: vec3<f32>;
-    [[location(1)]] normal: vec3<f32>;
-    [[location(2)]] uv: vec2<f32>;
-};
-
 struct VertexOutput {
     [[builtin(position)]] clip_position: vec4<f32>;
     [[location(0)]] normal: vec3<f32>;
     [[location(1)]] uv: vec2<f32>;
     [[location(2)]] color: vec4<f32>;
+#ifdef VERTEX_TANGENTS
+    [[location(3)]] world_tangent: vec4<f32>;
+#endif
 };
 
 [[stage(vertex)]]
@@ -41,6 +40,10 @@ fn vertex(vertex: Vertex) -> VertexOutput {
     out.uv = (vertex.uv + material.offset) * material.scale;
     // out.color = vec4<f32>(1.0, 0.0, 0.0, 1.0);
     out.color = material.color;
+#ifdef VERTEX_TANGENTS
+    let world_tangent = mesh.model * vec4<f32>(vertex.tangent.xyz, 0.0);
+    out.world_tangent = vec4<f32>(world_tangent.xyz, vertex.tangent.w);
+#endif
     return out;
 }
 
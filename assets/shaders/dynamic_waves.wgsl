@@ -0,0 +1,42 @@
+#import bevy_pbr::mesh_view_bind_group
+#import bevy_pbr::mesh_struct
+#import playground::globals
+#import playground::vertex
+
+// Used by a DynamicMaterial, the inspector is generated from this struct
+struct Waves {
+    // @color
+    color_a: vec4<f32>;
+    color_b: vec4<f32>; // @color
+    frequency: f32; // @range(0, 20)
+    // @range(0, 5)
+    speed: f32;
+    direction: vec2<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> material: Waves;
+
+[[group(2), binding(0)]]
+var<uniform> mesh: Mesh;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let world_position = mesh.model * vec4<f32>(vertex.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * world_position;
+    out.uv = vertex.uv;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let t = dot(in.uv, material.direction) * material.frequency + globals.time * material.speed;
+    return mix(material.color_a, material.color_b, sin(t) * 0.5 + 0.5);
+}
@@ -0,0 +1,18 @@
+#define_import_path playground::globals
+
+// Must match `Globals` in src/globals.rs
+struct Globals {
+    // Size of the window in physical pixels
+    resolution: vec2<f32>;
+    // Cursor position in physical pixels, the origin is the bottom left corner
+    cursor: vec2<f32>;
+    // Elapsed time in seconds
+    time: f32;
+    delta_time: f32;
+    frame: u32;
+    // 1 = left, 2 = right, 4 = middle
+    mouse_buttons: u32;
+};
+
+[[group(3), binding(0)]]
+var<uniform> globals: Globals;
@@ -1,5 +1,7 @@
 #import bevy_pbr::mesh_view_bind_group
 #import bevy_pbr::mesh_struct
+#import playground::globals
+#import playground::vertex
 
 struct CustomMaterial {
     color_a: vec4<f32>;
@@ -14,12 +16,6 @@ var<uniform> material: CustomMaterial;
 [[group(2), binding(0)]]
 var<uniform> mesh: Mesh;
 
-struct Vertex {
-    [[location(0)]] position: vec3<f32>;
-    [[location(1)]] normal: vec3<f32>;
-    [[location(2)]] uv: vec2<f32>;
-};
-
 struct VertexOutput {
     [[builtin(position)]] clip_position: vec4<f32>;
     [[location(0)]] uv: vec2<f32>;
@@ -50,7 +46,7 @@ fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     // t = fract(t);
     // return mix(material.color_a, material.color_b, vec4<f32>(t));
 
-    var x_offset = cos(in.uv.y * tau * 8.0) * 0.05;
+    var x_offset = cos((in.uv.y - globals.time * 0.1) * tau * 8.0) * 0.05;
     var t = cos((in.uv.x + x_offset) * tau * 5.0) * 0.5 + 0.5;
 
     // triangle wave
@@ -0,0 +1,32 @@
+#define_import_path playground::sdf
+
+// Signed distance functions, negative inside. Must match `Sdf::distance` in src/sdf.rs,
+// `Sdf::to_wgsl` writes the expressions calling them.
+
+fn sdf_sphere(p: vec3<f32>, radius: f32) -> f32 {
+    return length(p) - radius;
+}
+
+// A box centered on the origin
+fn sdf_cuboid(p: vec3<f32>, half_size: vec3<f32>) -> f32 {
+    let q = abs(p) - half_size;
+    return length(max(q, vec3<f32>(0.0))) + min(max(q.x, max(q.y, q.z)), 0.0);
+}
+
+// A torus lying on the XZ plane, around the Y axis
+fn sdf_torus(p: vec3<f32>, radius: f32, ring_radius: f32) -> f32 {
+    let q = vec2<f32>(length(p.xz) - radius, p.y);
+    return length(q) - ring_radius;
+}
+
+// A cylinder along the Y axis, centered on the origin
+fn sdf_cylinder(p: vec3<f32>, radius: f32, height: f32) -> f32 {
+    let d = abs(vec2<f32>(length(p.xz), p.y)) - vec2<f32>(radius, height * 0.5);
+    return min(max(d.x, d.y), 0.0) + length(max(d, vec2<f32>(0.0)));
+}
+
+// Union blending the surfaces closer than `k`
+fn sdf_smooth_union(a: f32, b: f32, k: f32) -> f32 {
+    let h = clamp(0.5 + 0.5 * (b - a) / k, 0.0, 1.0);
+    return mix(b, a, h) - k * h * (1.0 - h);
+}
@@ -0,0 +1,83 @@
+#import bevy_pbr::mesh_view_bind_group
+#import bevy_pbr::mesh_struct
+#import playground::vertex
+#import playground::sdf
+
+// Raymarches `scene_sdf` in the object space of the mesh, which should contain the whole shape.
+// Used on a cube next to the "SDF mesh" of the default scene to compare it with the mesh.
+struct Raymarch {
+    color: vec4<f32>; // @color
+    // @range(0, 1)
+    ambient: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> material: Raymarch;
+
+[[group(2), binding(0)]]
+var<uniform> mesh: Mesh;
+
+let MAX_STEPS: i32 = 128;
+let HIT_DISTANCE: f32 = 0.0005;
+let MAX_DISTANCE: f32 = 4.0;
+
+// The "SDF mesh" of scenes/default.scene.ron, written by `Sdf::to_wgsl`
+fn scene_sdf(p: vec3<f32>) -> f32 {
+    return sdf_smooth_union(max(sdf_cuboid(p, vec3<f32>(0.35, 0.35, 0.35)), -sdf_sphere(p, 0.45)), sdf_torus(p, 0.5, 0.08), 0.1);
+}
+
+fn scene_normal(p: vec3<f32>) -> vec3<f32> {
+    let e = vec2<f32>(0.001, 0.0);
+    return normalize(vec3<f32>(
+        scene_sdf(p + e.xyy) - scene_sdf(p - e.xyy),
+        scene_sdf(p + e.yxy) - scene_sdf(p - e.yxy),
+        scene_sdf(p + e.yyx) - scene_sdf(p - e.yyx),
+    ));
+}
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let world_position = mesh.model * vec4<f32>(vertex.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let inverse_model = transpose(mesh.inverse_transpose_model);
+    let camera = (inverse_model * vec4<f32>(view.world_position, 1.0)).xyz;
+    let start = (inverse_model * vec4<f32>(in.world_position, 1.0)).xyz;
+    let direction = normalize(start - camera);
+
+    // Marches from the front faces of the mesh, the shape is behind them
+    let origin = start;
+    var t = 0.0;
+    var hit = false;
+    for (var i = 0; i < MAX_STEPS; i = i + 1) {
+        let d = scene_sdf(origin + direction * t);
+        if (d < HIT_DISTANCE) {
+            hit = true;
+            break;
+        }
+        t = t + d;
+        if (t > MAX_DISTANCE) {
+            break;
+        }
+    }
+    if (!hit) {
+        discard;
+    }
+
+    let normal = normalize((mesh.inverse_transpose_model * vec4<f32>(scene_normal(origin + direction * t), 0.0)).xyz);
+    let light = normalize(vec3<f32>(0.5, 1.0, 0.3));
+    let diffuse = max(dot(normal, light), 0.0);
+    return vec4<f32>(material.color.rgb * (material.ambient + (1.0 - material.ambient) * diffuse), 1.0);
+}
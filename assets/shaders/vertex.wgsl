@@ -0,0 +1,13 @@
+#define_import_path playground::vertex
+
+// Vertex attributes of the playground meshes. Meshes with `Mesh::ATTRIBUTE_TANGENT`, like the
+// scene meshes which get them from `tangents::generate_tangents`, also define VERTEX_TANGENTS.
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+#ifdef VERTEX_TANGENTS
+    // W is the handedness, bitangent = cross(normal, tangent.xyz) * tangent.w
+    [[location(3)]] tangent: vec4<f32>;
+#endif
+};
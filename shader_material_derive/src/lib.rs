@@ -0,0 +1,664 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parenthesized, parse::ParseStream, parse_macro_input, punctuated::Punctuated, spanned::Spanned,
+    Data, DeriveInput, Expr, Field, Fields, Ident, Lit, LitInt, LitStr, Meta, NestedMeta, Token,
+};
+
+/// Generates the `RenderAsset`, `Material`, `ShaderMaterial` and `Inspect` impls for a material.
+/// The material then needs to be added with `ShaderMaterialPlugin`.
+///
+/// Every field without a `#[texture]` attribute is copied into a generated `<Material>Uniform`
+/// struct deriving `AsStd140`, which is bound at binding 0, so those fields need to be `Copy`.
+/// Their names, types and std140 offsets are checked against the WGSL struct at startup.
+/// It assumes that the shader file contains a vertex and fragment shader.
+///
+/// Supported attributes:
+/// - `#[shader("shaders/my_material.wgsl")]` path of the shader, required
+/// - `#[visibility(vertex, fragment)]` stages that can see the uniform, defaults to both
+/// - `#[texture(1)]` on an `Option<Handle<Image>>` field, binds the texture at binding 1.
+///   `None` falls back to a white texture.
+/// - `#[sampler(2)]` next to a `#[texture]`, binds the sampler of the image at binding 2.
+///   Use `#[sampler(2, filter = "nearest", address_mode = "repeat")]` to override it.
+/// - `#[visibility(vertex, fragment)]` next to a `#[texture]`, stages that can see the texture
+///   and its sampler, defaults to the fragment stage
+/// - `#[inspect(color)]` on a `Vec4` field, edits it with a color picker in the inspector
+/// - `#[inspect(range(0.0, 5.0))]` on a `f32` field, edits it with a slider in the inspector
+/// - `#[inspect(skip)]` hides a field from the inspector, texture fields are always hidden
+#[proc_macro_derive(
+    ShaderMaterial,
+    attributes(shader, visibility, texture, sampler, inspect)
+)]
+pub fn derive_shader_material(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct MaterialAttrs {
+    shader: LitStr,
+    visibility: TokenStream2,
+}
+
+struct TextureBinding<'a> {
+    field: &'a Ident,
+    binding: LitInt,
+    sampler: Option<SamplerBinding>,
+    visibility: TokenStream2,
+}
+
+struct SamplerBinding {
+    binding: LitInt,
+    /// `SamplerDescriptor` fields overriding the sampler of the image
+    descriptor: Vec<TokenStream2>,
+}
+
+/// How a field is edited in the inspector
+enum InspectKind {
+    /// Uses the `InspectField` impl of the field type
+    Default,
+    Color,
+    Range(TokenStream2, TokenStream2),
+    Skip,
+}
+
+fn parse_attrs(input: &DeriveInput) -> syn::Result<MaterialAttrs> {
+    let mut shader = None;
+    let mut visibility = None;
+
+    for attr in &input.attrs {
+        if attr.path.is_ident("shader") {
+            shader = Some(attr.parse_args::<LitStr>()?);
+        } else if attr.path.is_ident("visibility") {
+            visibility = Some(parse_visibility(&attr.parse_meta()?)?);
+        }
+    }
+
+    let shader = shader.ok_or_else(|| {
+        syn::Error::new(
+            input.ident.span(),
+            "missing #[shader(\"path/to/shader.wgsl\")] attribute",
+        )
+    })?;
+    let visibility = visibility.unwrap_or_else(|| quote!(ShaderStages::VERTEX_FRAGMENT));
+
+    Ok(MaterialAttrs { shader, visibility })
+}
+
+fn parse_visibility(meta: &Meta) -> syn::Result<TokenStream2> {
+    let list = match meta {
+        Meta::List(list) if !list.nested.is_empty() => list,
+        _ => {
+            return Err(syn::Error::new(
+                meta.span(),
+                "expected #[visibility(vertex, fragment)]",
+            ))
+        }
+    };
+
+    let mut stages = Vec::new();
+    for nested in &list.nested {
+        let stage = match nested {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("vertex") => quote!(VERTEX),
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("fragment") => quote!(FRAGMENT),
+            _ => {
+                return Err(syn::Error::new(
+                    nested.span(),
+                    "unknown shader stage, expected `vertex` or `fragment`",
+                ))
+            }
+        };
+        stages.push(stage);
+    }
+
+    Ok(quote!(#(ShaderStages::#stages)|*))
+}
+
+/// Parses `#[texture(1)]`, `#[sampler(2, ...)]` and `#[visibility(...)]` on a field.
+/// Returns `None` when the field is a plain uniform field.
+fn parse_texture(field: &Field) -> syn::Result<Option<TextureBinding<'_>>> {
+    let mut binding = None;
+    let mut sampler = None;
+    let mut visibility = None;
+
+    for attr in &field.attrs {
+        if attr.path.is_ident("texture") {
+            binding = Some(attr.parse_args::<LitInt>()?);
+        } else if attr.path.is_ident("sampler") {
+            sampler = Some(parse_sampler(&attr.parse_meta()?)?);
+        } else if attr.path.is_ident("visibility") {
+            visibility = Some(parse_visibility(&attr.parse_meta()?)?);
+        }
+    }
+
+    match (binding, sampler, visibility) {
+        (Some(binding), sampler, visibility) => Ok(Some(TextureBinding {
+            field: field.ident.as_ref().unwrap(),
+            binding,
+            sampler,
+            visibility: visibility.unwrap_or_else(|| quote!(ShaderStages::FRAGMENT)),
+        })),
+        (None, Some(_), _) => Err(syn::Error::new(
+            field.span(),
+            "#[sampler] can only be used on a field with a #[texture] attribute",
+        )),
+        (None, None, Some(_)) => Err(syn::Error::new(
+            field.span(),
+            "#[visibility] can only be used on a field with a #[texture] attribute, \
+             the visibility of the uniform is set on the struct",
+        )),
+        (None, None, None) => Ok(None),
+    }
+}
+
+/// Parses `#[inspect(color)]`, `#[inspect(range(min, max))]` or `#[inspect(skip)]` on a field
+fn parse_inspect(field: &Field) -> syn::Result<InspectKind> {
+    let attr = match field
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("inspect"))
+    {
+        Some(attr) => attr,
+        None => return Ok(InspectKind::Default),
+    };
+    let error = || {
+        syn::Error::new(
+            attr.span(),
+            "expected #[inspect(color)], #[inspect(range(min, max))] or #[inspect(skip)]",
+        )
+    };
+
+    attr.parse_args_with(|input: ParseStream| {
+        let kind: Ident = input.parse()?;
+        if kind == "color" {
+            Ok(InspectKind::Color)
+        } else if kind == "skip" {
+            Ok(InspectKind::Skip)
+        } else if kind == "range" {
+            let content;
+            parenthesized!(content in input);
+            let bounds = Punctuated::<Expr, Token![,]>::parse_terminated(&content)?;
+            match (bounds.first(), bounds.last()) {
+                (Some(min), Some(max)) if bounds.len() == 2 => {
+                    Ok(InspectKind::Range(quote!(#min), quote!(#max)))
+                }
+                _ => Err(error()),
+            }
+        } else {
+            Err(error())
+        }
+    })
+}
+
+/// Turns a field name like `color_start` into a label like `Color start`
+fn field_label(field: &Ident) -> String {
+    let name = field.to_string().replace('_', " ");
+    let mut chars = name.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+fn parse_sampler(meta: &Meta) -> syn::Result<SamplerBinding> {
+    let list = match meta {
+        Meta::List(list) => list,
+        _ => {
+            return Err(syn::Error::new(
+                meta.span(),
+                "expected #[sampler(binding, filter = \"..\", address_mode = \"..\")]",
+            ))
+        }
+    };
+
+    let mut nested = list.nested.iter();
+    let binding = match nested.next() {
+        Some(NestedMeta::Lit(Lit::Int(binding))) => binding.clone(),
+        _ => {
+            return Err(syn::Error::new(
+                list.span(),
+                "the first argument of #[sampler] must be its binding",
+            ))
+        }
+    };
+
+    let mut descriptor = Vec::new();
+    for setting in nested {
+        let (name, value) = match setting {
+            NestedMeta::Meta(Meta::NameValue(name_value)) => match &name_value.lit {
+                Lit::Str(value) => (&name_value.path, value),
+                lit => return Err(syn::Error::new(lit.span(), "expected a string")),
+            },
+            _ => {
+                return Err(syn::Error::new(
+                    setting.span(),
+                    "expected `name = \"value\"`",
+                ))
+            }
+        };
+
+        if name.is_ident("filter") {
+            let filter = match value.value().as_str() {
+                "linear" => quote!(FilterMode::Linear),
+                "nearest" => quote!(FilterMode::Nearest),
+                _ => {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        "unknown filter, expected \"linear\" or \"nearest\"",
+                    ))
+                }
+            };
+            descriptor.push(quote! {
+                mag_filter: #filter,
+                min_filter: #filter,
+                mipmap_filter: #filter,
+            });
+        } else if name.is_ident("address_mode") {
+            let address_mode = match value.value().as_str() {
+                "clamp" => quote!(AddressMode::ClampToEdge),
+                "repeat" => quote!(AddressMode::Repeat),
+                "mirror" => quote!(AddressMode::MirrorRepeat),
+                _ => {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        "unknown address mode, expected \"clamp\", \"repeat\" or \"mirror\"",
+                    ))
+                }
+            };
+            descriptor.push(quote! {
+                address_mode_u: #address_mode,
+                address_mode_v: #address_mode,
+                address_mode_w: #address_mode,
+            });
+        } else {
+            return Err(syn::Error::new(
+                name.span(),
+                "unknown sampler setting, expected `filter` or `address_mode`",
+            ));
+        }
+    }
+
+    Ok(SamplerBinding {
+        binding,
+        descriptor,
+    })
+}
+
+#[allow(clippy::too_many_lines)]
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let MaterialAttrs { shader, visibility } = parse_attrs(input)?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new(
+                    input.span(),
+                    "ShaderMaterial can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.span(),
+                "ShaderMaterial can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut uniform_fields = Vec::new();
+    let mut textures = Vec::new();
+    let mut inspect_fields = Vec::new();
+    for field in fields {
+        let inspect = parse_inspect(field)?;
+        match parse_texture(field)? {
+            Some(texture) => {
+                textures.push(texture);
+                continue;
+            }
+            None => uniform_fields.push(field),
+        }
+
+        let name = field.ident.as_ref().unwrap();
+        let label = field_label(name);
+        match inspect {
+            InspectKind::Skip => {}
+            InspectKind::Default => inspect_fields.push(quote! {
+                InspectField::inspect_field(&mut self.#name, ui, #label)
+            }),
+            InspectKind::Color => inspect_fields.push(quote! {
+                inspect_color(ui, #label, &mut self.#name)
+            }),
+            InspectKind::Range(min, max) => inspect_fields.push(quote! {
+                inspect_range(ui, #label, &mut self.#name, #min..=#max)
+            }),
+        }
+    }
+
+    let vis = &input.vis;
+    let material = &input.ident;
+    let gpu_material = format_ident!("Gpu{}", material);
+    let uniform = format_ident!("{}Uniform", material);
+    let label = material.to_string();
+
+    let mut layout_entries = Vec::new();
+    let mut bind_group_entries = Vec::new();
+    let mut prepare_textures = Vec::new();
+
+    let uniform_definition = if uniform_fields.is_empty() {
+        quote!()
+    } else {
+        let names: Vec<_> = uniform_fields.iter().map(|f| &f.ident).collect();
+        let types = uniform_fields.iter().map(|f| &f.ty);
+        let doc = format!("Uniform data of [`{}`] bound at binding 0", material);
+
+        layout_entries.push(quote! {
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: #visibility,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(#uniform::std140_size_static() as u64),
+                },
+                count: None,
+            }
+        });
+        bind_group_entries.push(quote! {
+            BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }
+        });
+
+        quote! {
+            #[doc = #doc]
+            #[derive(Debug, Clone, ::bevy::render::render_resource::std140::AsStd140)]
+            #vis struct #uniform {
+                #(pub #names: #types,)*
+            }
+
+            impl #material {
+                #vis fn uniform(&self) -> #uniform {
+                    #uniform {
+                        #(#names: self.#names,)*
+                    }
+                }
+            }
+        }
+    };
+
+    for TextureBinding {
+        field,
+        binding,
+        sampler,
+        visibility,
+    } in &textures
+    {
+        let texture_view = format_ident!("{}_texture_view", field);
+        let image_sampler = format_ident!("{}_image_sampler", field);
+
+        layout_entries.push(quote! {
+            BindGroupLayoutEntry {
+                binding: #binding,
+                visibility: #visibility,
+                ty: BindingType::Texture {
+                    multisampled: false,
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                },
+                count: None,
+            }
+        });
+        bind_group_entries.push(quote! {
+            BindGroupEntry {
+                binding: #binding,
+                resource: BindingResource::TextureView(#texture_view),
+            }
+        });
+        prepare_textures.push(quote! {
+            let (#texture_view, #image_sampler) = match &extracted_asset.#field {
+                Some(handle) => match images.get(handle) {
+                    Some(gpu_image) => (&gpu_image.texture_view, &gpu_image.sampler),
+                    None => return Err(PrepareAssetError::RetryNextUpdate(extracted_asset)),
+                },
+                None => {
+                    let white = &material_pipeline.mesh_pipeline.dummy_white_gpu_image;
+                    (&white.texture_view, &white.sampler)
+                }
+            };
+        });
+
+        let sampler = match sampler {
+            Some(sampler) => sampler,
+            None => continue,
+        };
+        let sampler_binding = &sampler.binding;
+        let sampler_resource = if sampler.descriptor.is_empty() {
+            quote!(#image_sampler)
+        } else {
+            let custom_sampler = format_ident!("{}_sampler", field);
+            let descriptor = &sampler.descriptor;
+            prepare_textures.push(quote! {
+                let #custom_sampler = render_device.create_sampler(&SamplerDescriptor {
+                    label: Some(#label),
+                    #(#descriptor)*
+                    ..Default::default()
+                });
+            });
+            quote!(&#custom_sampler)
+        };
+
+        layout_entries.push(quote! {
+            BindGroupLayoutEntry {
+                binding: #sampler_binding,
+                visibility: #visibility,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            }
+        });
+        bind_group_entries.push(quote! {
+            BindGroupEntry {
+                binding: #sampler_binding,
+                resource: BindingResource::Sampler(#sampler_resource),
+            }
+        });
+    }
+
+    let layout_members = uniform_fields.iter().map(|field| {
+        let name = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        quote! {
+            LayoutMember {
+                name: stringify!(#name).to_owned(),
+                ty: <#ty as WgslType>::WGSL.to_owned(),
+                // Doesn't create a reference to the uninitialized struct
+                offset: unsafe { addr_of!((*std140).#name) as usize - std140 as usize } as u32,
+            }
+        }
+    });
+    let uniform_layout = if uniform_fields.is_empty() {
+        quote!(Vec::new())
+    } else {
+        quote! {
+            let std140 = MaybeUninit::<<#uniform as AsStd140>::Output>::uninit();
+            let std140 = std140.as_ptr();
+            vec![#(#layout_members),*]
+        }
+    };
+
+    let texture_fields = textures.iter().map(|texture| texture.field);
+    let (prepare_buffer, buffer_field, buffer_field_definition, uniform_bytes, gpu_buffer) =
+        if uniform_fields.is_empty() {
+            (
+                quote!(),
+                quote!(),
+                quote!(),
+                quote!(Vec::new()),
+                quote!(None),
+            )
+        } else {
+            (
+                quote! {
+                    let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+                        contents: extracted_asset.uniform().as_std140().as_bytes(),
+                        label: Some(#label),
+                        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                    });
+                },
+                quote!(buffer,),
+                quote!(buffer: ::bevy::render::render_resource::Buffer,),
+                quote!(self.uniform().as_std140().as_bytes().to_vec()),
+                quote!(Some(&prepared.buffer)),
+            )
+        };
+
+    Ok(quote! {
+        #uniform_definition
+
+        #[derive(Clone)]
+        #vis struct #gpu_material {
+            #buffer_field_definition
+            textures: Vec<Option<::bevy::asset::Handle<::bevy::render::texture::Image>>>,
+            bind_group: ::bevy::render::render_resource::BindGroup,
+        }
+
+        const _: () = {
+            #[allow(unused_imports)]
+            use ::bevy::{
+                asset::{AssetServer, Handle},
+                ecs::system::{
+                    lifetimeless::{SRes, SResMut},
+                    SystemParamItem,
+                },
+                pbr::{Material, MaterialPipeline},
+                render::{
+                    render_asset::{PrepareAssetError, RenderAsset, RenderAssets},
+                    render_resource::{
+                        std140::{AsStd140, Std140},
+                        AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry,
+                        BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+                        BindingResource, BindingType, BufferBindingType, BufferInitDescriptor,
+                        BufferSize, BufferUsages, FilterMode, SamplerBindingType,
+                        SamplerDescriptor, Shader, ShaderStages, TextureSampleType,
+                        TextureViewDimension,
+                    },
+                    render_resource::Buffer,
+                    renderer::RenderDevice,
+                    texture::Image,
+                },
+            };
+            #[allow(unused_imports)]
+            use std::{mem::MaybeUninit, ptr::addr_of};
+            use crate::{
+                inspector::{inspect_color, inspect_range, Inspect, InspectField},
+                layout_check::{LayoutMember, MaterialLayout, WgslType},
+                shader_material::{ShaderMaterial, UpdatedMaterials},
+            };
+
+            impl #material {
+                /// The entries of the bind group layout at `group(1)`
+                #vis fn bind_group_layout_entries() -> Vec<BindGroupLayoutEntry> {
+                    vec![#(#layout_entries),*]
+                }
+            }
+
+            impl Inspect for #material {
+                #[allow(unused_variables, unused_mut)]
+                fn inspect(&mut self, ui: &mut ::bevy_egui::egui::Ui) -> bool {
+                    let mut changed = false;
+                    #(changed |= #inspect_fields;)*
+                    changed
+                }
+            }
+
+            impl ShaderMaterial for #material {
+                fn uniform_bytes(&self) -> Vec<u8> {
+                    #uniform_bytes
+                }
+
+                fn textures(&self) -> Vec<Option<Handle<Image>>> {
+                    vec![#(self.#texture_fields.as_ref().map(Handle::clone_weak)),*]
+                }
+
+                fn gpu_buffer(prepared: &Self::PreparedAsset) -> Option<&Buffer> {
+                    #gpu_buffer
+                }
+
+                fn gpu_textures(prepared: &Self::PreparedAsset) -> &[Option<Handle<Image>>] {
+                    &prepared.textures
+                }
+
+                fn uniform_layout() -> Option<MaterialLayout> {
+                    Some(MaterialLayout {
+                        shader: #shader,
+                        members: { #uniform_layout },
+                    })
+                }
+            }
+
+            impl RenderAsset for #material {
+                type ExtractedAsset = #material;
+                type PreparedAsset = #gpu_material;
+                type Param = (
+                    SRes<RenderDevice>,
+                    SRes<MaterialPipeline<Self>>,
+                    SRes<RenderAssets<Image>>,
+                    SResMut<UpdatedMaterials<Self>>,
+                );
+
+                fn extract_asset(&self) -> Self::ExtractedAsset {
+                    self.clone()
+                }
+
+                #[allow(unused_variables)]
+                fn prepare_asset(
+                    extracted_asset: Self::ExtractedAsset,
+                    (render_device, material_pipeline, images, updated_materials): &mut SystemParamItem<
+                        Self::Param,
+                    >,
+                ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+                    // Only the uniform values changed, the buffer was already updated in place
+                    if let Some(prepared) = updated_materials.take(&extracted_asset) {
+                        return Ok(prepared);
+                    }
+
+                    #(#prepare_textures)*
+                    #prepare_buffer
+                    let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+                        entries: &[#(#bind_group_entries),*],
+                        label: None,
+                        layout: &material_pipeline.material_layout,
+                    });
+                    Ok(#gpu_material {
+                        #buffer_field
+                        textures: extracted_asset.textures(),
+                        bind_group,
+                    })
+                }
+            }
+
+            impl Material for #material {
+                fn vertex_shader(asset_server: &AssetServer) -> Option<Handle<Shader>> {
+                    Some(asset_server.load(#shader))
+                }
+
+                fn fragment_shader(asset_server: &AssetServer) -> Option<Handle<Shader>> {
+                    Some(asset_server.load(#shader))
+                }
+
+                fn bind_group(render_asset: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+                    &render_asset.bind_group
+                }
+
+                fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+                    render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                        entries: &Self::bind_group_layout_entries(),
+                        label: None,
+                    })
+                }
+            }
+        };
+    })
+}
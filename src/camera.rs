@@ -1,117 +1,648 @@
+use std::{
+    f32::consts::{PI, TAU},
+    fs,
+    ops::{Add, Mul, Sub},
+    path::PathBuf,
+};
+
+use anyhow::Context;
 use bevy::{
+    asset::FileAssetIo,
     input::mouse::{MouseMotion, MouseWheel},
     prelude::*,
 };
-use bevy_egui::EguiContext;
+use bevy_egui::{
+    egui::{self, Ui},
+    EguiContext,
+};
+use serde::{Deserialize, Serialize};
 
-#[derive(Component)]
-pub struct PanOrbitCamera {
-    /// The "focus point" to orbit around. It is automatically updated when panning the camera
+use crate::fly_camera::{FlyCamera, FlyControls};
+
+/// File under `assets/` the camera controls are saved to from the inspector
+const CONTROLS_FILE: &str = "settings/camera_controls.ron";
+
+/// Seconds of mouse motion averaged to get the velocity kept by the momentum
+const VELOCITY_SMOOTHING: f32 = 0.05;
+
+/// Position of an orbiting camera, looking at `focus` from `radius` away
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Orbit {
     pub focus: Vec3,
     pub radius: f32,
-    pub upside_down: bool,
+    /// Rotation around the global Y axis, in radians
+    pub yaw: f32,
+    /// Rotation around the local X axis, in radians. The camera is upside down past ±90°
+    pub pitch: f32,
+    /// Half the height of the view in orthographic projection, which zooms instead of `radius`
+    pub scale: f32,
 }
 
-impl Default for PanOrbitCamera {
+impl Default for Orbit {
     fn default() -> Self {
-        PanOrbitCamera {
+        Self {
             focus: Vec3::ZERO,
             radius: 5.0,
-            upside_down: false,
+            yaw: 0.0,
+            pitch: 0.0,
+            scale: matching_scale(5.0, PerspectiveProjection::default().fov),
         }
     }
 }
 
-/// Pan the camera with middle mouse click, zoom with scroll wheel, orbit with right mouse click.
-#[allow(clippy::needless_pass_by_value)]
-pub fn pan_orbit_camera(
-    windows: Res<Windows>,
-    mut ev_motion: EventReader<MouseMotion>,
-    mut ev_scroll: EventReader<MouseWheel>,
-    input_mouse: Res<Input<MouseButton>>,
-    mut query: Query<(&mut PanOrbitCamera, &mut Transform, &PerspectiveProjection)>,
-    mut egui_context: ResMut<EguiContext>,
-) {
-    if egui_context.ctx_mut().is_pointer_over_area() {
-        return;
+impl Orbit {
+    /// The orbit of a camera at `transform` around `focus`, which should be in front of it
+    pub fn from_transform(transform: &Transform, focus: Vec3) -> Self {
+        let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        let radius = transform.translation.distance(focus);
+        Self {
+            focus,
+            radius,
+            yaw,
+            pitch,
+            scale: matching_scale(radius, PerspectiveProjection::default().fov),
+        }
+    }
+
+    pub fn rotation(&self) -> Quat {
+        // emulating parent/child to make the yaw/y-axis rotation behave like a turntable
+        Quat::from_rotation_y(self.yaw) * Quat::from_rotation_x(self.pitch)
+    }
+
+    pub fn transform(&self) -> Transform {
+        let rotation = self.rotation();
+        Transform {
+            translation: self.focus + rotation * Vec3::new(0.0, 0.0, self.radius),
+            rotation,
+            ..Default::default()
+        }
+    }
+
+    /// Moves toward `target` by `factor`, 1 reaches it
+    fn damp(&mut self, target: &Orbit, factor: Vec3) {
+        let [orbit, pan, zoom] = factor.to_array();
+        self.yaw += (target.yaw - self.yaw) * orbit;
+        self.pitch += (target.pitch - self.pitch) * orbit;
+        self.focus = self.focus.lerp(target.focus, pan);
+        self.radius += (target.radius - self.radius) * zoom;
+        self.scale += (target.scale - self.scale) * zoom;
+    }
+
+    /// The orbit `t` of the way to `to`, turning the shortest way around
+    #[must_use]
+    pub fn lerp(&self, to: &Orbit, t: f32) -> Self {
+        let turn = |from: f32, target: f32| from + ((target - from + PI).rem_euclid(TAU) - PI) * t;
+        Self {
+            focus: self.focus.lerp(to.focus, t),
+            radius: self.radius + (to.radius - self.radius) * t,
+            yaw: turn(self.yaw, to.yaw),
+            pitch: turn(self.pitch, to.pitch),
+            scale: self.scale + (to.scale - self.scale) * t,
+        }
+    }
+}
+
+/// Orthographic scale showing as much of the focus plane as a perspective projection with `fov`
+/// from `radius` away
+fn matching_scale(radius: f32, fov: f32) -> f32 {
+    radius * (fov * 0.5).tan()
+}
+
+/// Transition of the target orbit started by [`PanOrbitCamera::animate_to`]
+#[derive(Debug, Clone, Copy)]
+struct Animation {
+    from: Orbit,
+    to: Orbit,
+    elapsed: f32,
+    duration: f32,
+}
+
+/// The inputs move the `target` orbit and the camera follows it smoothly
+#[derive(Component, Default)]
+pub struct PanOrbitCamera {
+    /// The orbit the camera moves to. The "focus point" is automatically updated when panning
+    /// the camera
+    pub target: Orbit,
+    /// The orbit of the camera this frame
+    pub current: Orbit,
+    pub upside_down: bool,
+    /// Yaw and pitch speed kept after releasing the orbit button, in radians per second
+    orbit_velocity: Vec2,
+    /// Focus point speed kept after releasing the pan button
+    pan_velocity: Vec3,
+    animation: Option<Animation>,
+}
+
+impl PanOrbitCamera {
+    pub fn new(orbit: Orbit) -> Self {
+        Self {
+            target: orbit,
+            current: orbit,
+            ..Default::default()
+        }
+    }
+
+    /// Moves the camera to `orbit` right away, without smoothing or momentum
+    pub fn jump_to(&mut self, orbit: Orbit) {
+        *self = Self::new(orbit);
+    }
+
+    /// Moves the target orbit to `orbit` over `duration` seconds, easing in and out. Orbiting,
+    /// panning or zooming cancels it.
+    pub fn animate_to(&mut self, orbit: Orbit, duration: f32) {
+        self.orbit_velocity = Vec2::ZERO;
+        self.pan_velocity = Vec3::ZERO;
+        self.animation = Some(Animation {
+            from: self.target,
+            to: orbit,
+            elapsed: 0.0,
+            duration,
+        });
+    }
+
+    /// Sets the orthographic scale of the orbits to show as much as the perspective projection
+    /// with `fov` does, before switching to orthographic projection
+    pub fn match_perspective(&mut self, fov: f32) {
+        for orbit in self.orbits_mut() {
+            orbit.scale = matching_scale(orbit.radius, fov);
+        }
+    }
+
+    /// Sets the radius of the orbits for the perspective projection with `fov` to show as much
+    /// as the orthographic projection does, before switching to perspective projection
+    pub fn match_orthographic(&mut self, fov: f32) {
+        for orbit in self.orbits_mut() {
+            orbit.radius = orbit.scale / matching_scale(1.0, fov);
+        }
+    }
+
+    fn orbits_mut(&mut self) -> impl Iterator<Item = &mut Orbit> {
+        let animation = self.animation.iter_mut();
+        [&mut self.target, &mut self.current]
+            .into_iter()
+            .chain(animation.flat_map(|animation| [&mut animation.from, &mut animation.to]))
+    }
+}
+
+/// Modifier key held with a binding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Modifier {
+    None,
+    Shift,
+    Control,
+    Alt,
+}
+
+impl Modifier {
+    pub const ALL: [Self; 4] = [Self::None, Self::Shift, Self::Control, Self::Alt];
+
+    fn keys(self) -> &'static [KeyCode] {
+        match self {
+            Self::None => &[],
+            Self::Shift => &[KeyCode::LShift, KeyCode::RShift],
+            Self::Control => &[KeyCode::LControl, KeyCode::RControl],
+            Self::Alt => &[KeyCode::LAlt, KeyCode::RAlt],
+        }
+    }
+
+    /// Whether this modifier is held, regardless of the other ones. Always false for `None`
+    pub fn held(self, keys: &Input<KeyCode>) -> bool {
+        keys.any_pressed(self.keys().iter().copied())
+    }
+
+    /// Whether this modifier is the only one held, `None` is active when no modifier is held
+    fn active(self, keys: &Input<KeyCode>) -> bool {
+        Self::ALL
+            .iter()
+            .all(|modifier| modifier.held(keys) == (*modifier == self) || *modifier == Self::None)
+    }
+}
+
+/// A mouse button held with a modifier key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MouseBinding {
+    pub button: MouseButton,
+    pub modifier: Modifier,
+}
+
+impl MouseBinding {
+    pub fn new(button: MouseButton, modifier: Modifier) -> Self {
+        Self { button, modifier }
+    }
+
+    fn pressed(self, mouse: &Input<MouseButton>, keys: &Input<KeyCode>) -> bool {
+        mouse.pressed(self.button) && self.modifier.active(keys)
+    }
+}
+
+/// Keys moving the camera, the direction keys orbit, or pan while `pan_modifier` is held
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub up: KeyCode,
+    pub down: KeyCode,
+    pub pan_modifier: Modifier,
+    pub zoom_in: KeyCode,
+    pub zoom_out: KeyCode,
+    /// Fits the selected entity in view, or the whole scene when nothing is selected
+    pub frame: KeyCode,
+    /// Switches between perspective and orthographic projection
+    pub projection: KeyCode,
+    /// Orthographic views looking along -Z, -X and -Y, or the opposite way while
+    /// `opposite_view` is held
+    pub front_view: KeyCode,
+    pub side_view: KeyCode,
+    pub top_view: KeyCode,
+    pub opposite_view: Modifier,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            left: KeyCode::Left,
+            right: KeyCode::Right,
+            up: KeyCode::Up,
+            down: KeyCode::Down,
+            pan_modifier: Modifier::Shift,
+            zoom_in: KeyCode::Equals,
+            zoom_out: KeyCode::Minus,
+            frame: KeyCode::F,
+            projection: KeyCode::Numpad5,
+            front_view: KeyCode::Numpad1,
+            side_view: KeyCode::Numpad3,
+            top_view: KeyCode::Numpad7,
+            opposite_view: Modifier::Control,
+        }
+    }
+}
+
+/// Input mapping of a [`PanOrbitCamera`], edited in the inspector and saved to
+/// `assets/settings/camera_controls.ron`
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct PanOrbitCameraControls {
+    /// Any of these bindings orbits the camera, orbiting takes precedence over panning
+    pub orbit: Vec<MouseBinding>,
+    pub pan: Vec<MouseBinding>,
+    pub keys: KeyBindings,
+    /// Dragging across the whole window turns the camera by this many full turns horizontally,
+    /// and half turns vertically
+    pub orbit_sensitivity: f32,
+    /// 1 keeps the focus point under the cursor while panning
+    pub pan_sensitivity: f32,
+    /// Fraction of the distance to the focus point zoomed by each scroll wheel step
+    pub zoom_sensitivity: f32,
+    /// Radians per second
+    pub key_orbit_speed: f32,
+    /// Window sizes per second
+    pub key_pan_speed: f32,
+    /// Fraction of the distance to the focus point per second
+    pub key_zoom_speed: f32,
+    /// Seconds the camera takes to move about two thirds of the way to its target orientation,
+    /// 0 disables the smoothing
+    pub orbit_smoothing: f32,
+    /// Same as `orbit_smoothing` for the focus point
+    pub pan_smoothing: f32,
+    /// Same as `orbit_smoothing` for the distance to the focus point
+    pub zoom_smoothing: f32,
+    /// Orbiting and panning keep going after releasing the mouse button, slowing down for about
+    /// this many seconds. 0 disables the momentum
+    pub momentum: f32,
+    /// Keys and speeds of the fly mode
+    pub fly: FlyControls,
+    pub invert_orbit_x: bool,
+    pub invert_orbit_y: bool,
+    pub invert_pan: bool,
+    pub invert_zoom: bool,
+}
+
+impl Default for PanOrbitCameraControls {
+    fn default() -> Self {
+        Self {
+            orbit: vec![
+                MouseBinding::new(MouseButton::Right, Modifier::None),
+                MouseBinding::new(MouseButton::Left, Modifier::Alt),
+            ],
+            pan: vec![
+                MouseBinding::new(MouseButton::Middle, Modifier::None),
+                MouseBinding::new(MouseButton::Left, Modifier::Shift),
+            ],
+            keys: KeyBindings::default(),
+            orbit_sensitivity: 1.0,
+            pan_sensitivity: 1.0,
+            zoom_sensitivity: 0.2,
+            key_orbit_speed: 1.5,
+            key_pan_speed: 0.5,
+            key_zoom_speed: 1.0,
+            orbit_smoothing: 0.06,
+            pan_smoothing: 0.06,
+            zoom_smoothing: 0.1,
+            momentum: 0.0,
+            fly: FlyControls::default(),
+            invert_orbit_x: false,
+            invert_orbit_y: false,
+            invert_pan: false,
+            invert_zoom: false,
+        }
     }
+}
 
-    // change input mapping for orbit and panning here
-    let orbit_button = MouseButton::Right;
-    let pan_button = MouseButton::Middle;
+fn controls_path() -> PathBuf {
+    FileAssetIo::get_root_path()
+        .join("assets")
+        .join(CONTROLS_FILE)
+}
 
-    let mut pan = Vec2::ZERO;
-    let mut rotation_move = Vec2::ZERO;
-    let mut scroll = 0.0;
-    let mut orbit_button_changed = false;
+impl PanOrbitCameraControls {
+    /// The saved controls, or the default ones if they were never saved
+    pub fn load() -> Self {
+        let path = controls_path();
+        if !path.exists() {
+            return Self::default();
+        }
+        let controls = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))
+            .and_then(|text| Ok(ron::from_str::<Self>(&text)?));
+        controls.unwrap_or_else(|err| {
+            error!("Failed to load the camera controls: {:?}", err);
+            Self::default()
+        })
+    }
 
-    if input_mouse.pressed(orbit_button) {
-        for ev in ev_motion.iter() {
-            rotation_move += ev.delta;
+    fn save(&self) -> anyhow::Result<()> {
+        let path = controls_path();
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
         }
-    } else if input_mouse.pressed(pan_button) {
-        // Pan only if we're not rotating at the moment
-        for ev in ev_motion.iter() {
-            pan += ev.delta;
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(&path, text).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    fn any_pressed(
+        bindings: &[MouseBinding],
+        mouse: &Input<MouseButton>,
+        keys: &Input<KeyCode>,
+    ) -> bool {
+        bindings.iter().any(|binding| binding.pressed(mouse, keys))
+    }
+
+    /// Direction of the direction keys, +Y is down like the mouse motion
+    fn key_direction(&self, keys: &Input<KeyCode>) -> Vec2 {
+        Vec2::new(
+            key_axis(keys, self.keys.left, self.keys.right),
+            key_axis(keys, self.keys.up, self.keys.down),
+        )
+    }
+
+    /// Measures the velocity of the `motion` of this frame while its button is `held`, then adds
+    /// the velocity kept by the momentum to it after the button is released until it slows down
+    fn apply_momentum<T>(&self, velocity: &mut T, motion: T, held: bool, delta_time: f32) -> T
+    where
+        T: Copy + Default + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+    {
+        if self.momentum <= 0.0 {
+            *velocity = T::default();
+            return motion;
         }
+        if !held {
+            let motion = motion + *velocity * delta_time;
+            *velocity = *velocity * (-delta_time / self.momentum).exp();
+            return motion;
+        }
+        if delta_time > 0.0 {
+            let measure = 1.0 - (-delta_time / VELOCITY_SMOOTHING).exp();
+            *velocity = *velocity + (motion * (1.0 / delta_time) - *velocity) * measure;
+        }
+        motion
     }
-    for ev in ev_scroll.iter() {
-        scroll += ev.y;
+
+    /// Fraction of the way to the target orbit covered this frame by each smoothing
+    fn damping(&self, delta_time: f32) -> Vec3 {
+        Vec3::new(
+            damping_factor(self.orbit_smoothing, delta_time),
+            damping_factor(self.pan_smoothing, delta_time),
+            damping_factor(self.zoom_smoothing, delta_time),
+        )
+    }
+
+    fn direction_keys(&self) -> [KeyCode; 4] {
+        [
+            self.keys.left,
+            self.keys.right,
+            self.keys.up,
+            self.keys.down,
+        ]
     }
-    if input_mouse.just_released(orbit_button) || input_mouse.just_pressed(orbit_button) {
-        orbit_button_changed = true;
+}
+
+/// Fraction of the way to a target covered in `delta_time` by an exponential smoothing with the
+/// time constant `smoothing`, the same whatever the frame rate
+pub fn damping_factor(smoothing: f32, delta_time: f32) -> f32 {
+    if smoothing > 0.0 {
+        1.0 - (-delta_time / smoothing).exp()
+    } else {
+        1.0
     }
+}
 
-    for (mut pan_orbit, mut transform, projection) in query.iter_mut() {
-        if orbit_button_changed {
+/// -1 when only `negative` is pressed, 1 when only `positive` is
+fn key_axis(keys: &Input<KeyCode>, negative: KeyCode, positive: KeyCode) -> f32 {
+    match (keys.pressed(negative), keys.pressed(positive)) {
+        (true, false) => -1.0,
+        (false, true) => 1.0,
+        _ => 0.0,
+    }
+}
+
+/// Pan, orbit and zoom the camera with the mouse and keyboard bindings of its
+/// [`PanOrbitCameraControls`]. By default: orbit with right click or Alt + left click, pan with
+/// middle click or Shift + left click, zoom with the scroll wheel.
+/// The inputs move the target orbit, and the camera follows it with the smoothing of the controls.
+#[allow(
+    clippy::needless_pass_by_value,
+    clippy::type_complexity,
+    clippy::too_many_arguments,
+    clippy::too_many_lines
+)]
+pub fn pan_orbit_camera(
+    windows: Res<Windows>,
+    time: Res<Time>,
+    mut ev_motion: EventReader<MouseMotion>,
+    mut ev_scroll: EventReader<MouseWheel>,
+    input_mouse: Res<Input<MouseButton>>,
+    input_keys: Res<Input<KeyCode>>,
+    mut query: Query<
+        (
+            &mut PanOrbitCamera,
+            &PanOrbitCameraControls,
+            &mut Transform,
+            Option<&PerspectiveProjection>,
+            Option<&mut OrthographicProjection>,
+        ),
+        Without<FlyCamera>,
+    >,
+    mut egui_context: ResMut<EguiContext>,
+) {
+    let ctx = egui_context.ctx_mut();
+    let use_mouse = !ctx.is_pointer_over_area();
+    let use_keys = !ctx.wants_keyboard_input();
+
+    let motion: Vec2 = ev_motion.iter().map(|ev| &ev.delta).sum();
+    let scroll: f32 = ev_scroll.iter().map(|ev| ev.y).sum();
+    let window = get_primary_window_size(&windows);
+    let delta_time = time.delta_seconds();
+
+    for (mut pan_orbit, controls, mut transform, perspective, mut orthographic) in query.iter_mut()
+    {
+        // Size of the view at the focus point, panning by a window size moves the focus by it
+        let view = if let Some(orthographic) = &orthographic {
+            let size = Vec2::new(
+                orthographic.right - orthographic.left,
+                orthographic.top - orthographic.bottom,
+            );
+            size * orthographic.scale
+        } else if let Some(perspective) = perspective {
+            let fov = Vec2::new(perspective.fov * perspective.aspect_ratio, perspective.fov);
+            fov * pan_orbit.current.radius
+        } else {
+            continue;
+        };
+
+        let orbiting = use_mouse
+            && PanOrbitCameraControls::any_pressed(&controls.orbit, &input_mouse, &input_keys);
+        let panning = use_mouse
+            && !orbiting
+            && PanOrbitCameraControls::any_pressed(&controls.pan, &input_mouse, &input_keys);
+        let orbit_input_changed = controls.orbit.iter().any(|binding| {
+            input_mouse.just_released(binding.button) || input_mouse.just_pressed(binding.button)
+        }) || input_keys.any_just_pressed(controls.direction_keys())
+            || input_keys.any_just_released(controls.direction_keys());
+        if orbit_input_changed {
             // only check for upside down when orbiting started or ended this frame
             // if the camera is "upside" down, panning horizontally would be inverted, so invert the input to make it correct
             let up = transform.rotation * Vec3::Y;
             pan_orbit.upside_down = up.y <= 0.0;
         }
 
-        let mut any = false;
+        // Yaw and pitch in radians, pan in window sizes, zoom in fractions of the radius or scale
+        let mut rotation_move = Vec2::ZERO;
+        let mut pan = Vec2::ZERO;
+        let mut zoom = 0.0;
+        if orbiting && window.x > 0.0 && window.y > 0.0 {
+            rotation_move += motion / window
+                * Vec2::new(std::f32::consts::PI * 2.0, std::f32::consts::PI)
+                * controls.orbit_sensitivity;
+        } else if panning && window.x > 0.0 && window.y > 0.0 {
+            // Pan only if we're not rotating at the moment
+            pan += motion / window * controls.pan_sensitivity;
+        }
+        if use_mouse {
+            zoom += scroll * controls.zoom_sensitivity;
+        }
+        if use_keys {
+            // The direction keys act like dragging the mouse in their direction
+            let direction = controls.key_direction(&input_keys);
+            if controls.keys.pan_modifier != Modifier::None
+                && controls.keys.pan_modifier.active(&input_keys)
+            {
+                pan += direction * controls.key_pan_speed * delta_time;
+            } else if Modifier::None.active(&input_keys) {
+                rotation_move += direction * controls.key_orbit_speed * delta_time;
+            }
+            let zoom_direction =
+                key_axis(&input_keys, controls.keys.zoom_out, controls.keys.zoom_in);
+            zoom += zoom_direction * controls.key_zoom_speed * delta_time;
+        }
+        if controls.invert_orbit_x {
+            rotation_move.x = -rotation_move.x;
+        }
+        if controls.invert_orbit_y {
+            rotation_move.y = -rotation_move.y;
+        }
+        if controls.invert_pan {
+            pan = -pan;
+        }
+        if controls.invert_zoom {
+            zoom = -zoom;
+        }
+
+        // The mouse motion of the last frames is kept while dragging, and applied after the
+        // button is released until it slows down
+        let mut orbit_velocity = pan_orbit.orbit_velocity;
+        let rotation_move =
+            controls.apply_momentum(&mut orbit_velocity, rotation_move, orbiting, delta_time);
+        pan_orbit.orbit_velocity = orbit_velocity;
+
         if rotation_move.length_squared() > 0.0 {
-            any = true;
-            let window = get_primary_window_size(&windows);
-            let delta_x = {
-                let delta = rotation_move.x / window.x * std::f32::consts::PI * 2.0;
-                if pan_orbit.upside_down {
-                    -delta
-                } else {
-                    delta
-                }
+            let delta_x = if pan_orbit.upside_down {
+                -rotation_move.x
+            } else {
+                rotation_move.x
             };
-            let delta_y = rotation_move.y / window.y * std::f32::consts::PI;
-            let yaw = Quat::from_rotation_y(-delta_x);
-            let pitch = Quat::from_rotation_x(-delta_y);
-            transform.rotation = yaw * transform.rotation; // rotate around global y axis
-            transform.rotation *= pitch; // rotate around local x axis
-        } else if pan.length_squared() > 0.0 {
-            any = true;
-            // make panning distance independent of resolution and FOV,
-            let window = get_primary_window_size(&windows);
-            pan *= Vec2::new(projection.fov * projection.aspect_ratio, projection.fov) / window;
+            pan_orbit.target.yaw -= delta_x;
+            pan_orbit.target.pitch -= rotation_move.y;
+        }
+        let mut translation = Vec3::ZERO;
+        if pan.length_squared() > 0.0 {
+            // make panning distance independent of resolution, FOV and distance away from
+            // focus point
+            pan *= view;
             // translate by local axes
             let right = transform.rotation * Vec3::X * -pan.x;
             let up = transform.rotation * Vec3::Y * pan.y;
-            // make panning proportional to distance away from focus point
-            let translation = (right + up) * pan_orbit.radius;
-            pan_orbit.focus += translation;
-        } else if scroll.abs() > 0.0 {
-            any = true;
-            pan_orbit.radius -= scroll * pan_orbit.radius * 0.2;
+            translation = right + up;
+        }
+        let mut pan_velocity = pan_orbit.pan_velocity;
+        let translation =
+            controls.apply_momentum(&mut pan_velocity, translation, panning, delta_time);
+        pan_orbit.pan_velocity = pan_velocity;
+        pan_orbit.target.focus += translation;
+        if zoom.abs() > 0.0 {
             // dont allow zoom to reach zero or you get stuck
-            pan_orbit.radius = f32::max(pan_orbit.radius, 0.05);
+            if orthographic.is_some() {
+                pan_orbit.target.scale -= zoom * pan_orbit.target.scale;
+                pan_orbit.target.scale = f32::max(pan_orbit.target.scale, 0.01);
+            } else {
+                pan_orbit.target.radius -= zoom * pan_orbit.target.radius;
+                pan_orbit.target.radius = f32::max(pan_orbit.target.radius, 0.05);
+            }
+        }
+
+        if let Some(mut animation) = pan_orbit.animation {
+            let moved = rotation_move.length_squared() > 0.0
+                || translation.length_squared() > 0.0
+                || zoom.abs() > 0.0;
+            animation.elapsed += delta_time;
+            let t = (animation.elapsed / animation.duration).min(1.0);
+            if moved || t >= 1.0 {
+                pan_orbit.animation = None;
+            } else {
+                pan_orbit.animation = Some(animation);
+            }
+            if !moved {
+                let ease = t * t * (3.0 - 2.0 * t);
+                pan_orbit.target = animation.from.lerp(&animation.to, ease);
+            }
         }
 
-        if any {
-            // emulating parent/child to make the yaw/y-axis rotation behave like a turntable
-            // parent = x and y rotation
-            // child = z-offset
-            let rot_matrix = Mat3::from_quat(transform.rotation);
-            transform.translation =
-                pan_orbit.focus + rot_matrix.mul_vec3(Vec3::new(0.0, 0.0, pan_orbit.radius));
+        let target = pan_orbit.target;
+        pan_orbit
+            .current
+            .damp(&target, controls.damping(delta_time));
+        let orbit = pan_orbit.current.transform();
+        // Only written when moving so the transform isn't marked as changed every frame
+        if transform.translation != orbit.translation || transform.rotation != orbit.rotation {
+            transform.translation = orbit.translation;
+            transform.rotation = orbit.rotation;
+        }
+        if let Some(orthographic) = &mut orthographic {
+            // The projection is recomputed when changed
+            if (orthographic.scale - pan_orbit.current.scale).abs() > 0.0 {
+                orthographic.scale = pan_orbit.current.scale;
+            }
         }
     }
 }
@@ -123,3 +654,249 @@ fn get_primary_window_size(windows: &Res<Windows>) -> Vec2 {
         Vec2::ZERO
     }
 }
+
+/// The key binding waiting for a key press in the inspector, by label
+#[derive(Default)]
+pub struct RebindingKey(Option<&'static str>);
+
+/// Bindings, sensitivities and invert flags of the camera, with save and reset buttons
+pub fn inspector(
+    ui: &mut Ui,
+    controls: &mut PanOrbitCameraControls,
+    rebinding: &mut RebindingKey,
+    pressed_key: Option<KeyCode>,
+) {
+    mouse_bindings(ui, "Orbit", &mut controls.orbit);
+    mouse_bindings(ui, "Pan", &mut controls.pan);
+
+    for (label, key) in [
+        ("Left", &mut controls.keys.left),
+        ("Right", &mut controls.keys.right),
+        ("Up", &mut controls.keys.up),
+        ("Down", &mut controls.keys.down),
+        ("Zoom in", &mut controls.keys.zoom_in),
+        ("Zoom out", &mut controls.keys.zoom_out),
+        ("Frame selection", &mut controls.keys.frame),
+        ("Perspective/orthographic", &mut controls.keys.projection),
+        ("Front view", &mut controls.keys.front_view),
+        ("Side view", &mut controls.keys.side_view),
+        ("Top view", &mut controls.keys.top_view),
+        ("Fly mode", &mut controls.fly.toggle),
+        ("Fly forward", &mut controls.fly.forward),
+        ("Fly backward", &mut controls.fly.backward),
+        ("Fly left", &mut controls.fly.left),
+        ("Fly right", &mut controls.fly.right),
+        ("Fly up", &mut controls.fly.up),
+        ("Fly down", &mut controls.fly.down),
+    ] {
+        ui.horizontal(|ui| {
+            ui.label(format!("{} key: ", label));
+            let waiting = rebinding.0 == Some(label);
+            let text = if waiting {
+                "Press a key...".to_owned()
+            } else {
+                format!("{:?}", key)
+            };
+            if ui.selectable_label(waiting, text).clicked() {
+                rebinding.0 = if waiting { None } else { Some(label) };
+            } else if let (true, Some(pressed)) = (waiting, pressed_key) {
+                *key = pressed;
+                rebinding.0 = None;
+            }
+        });
+    }
+    modifier_combo(ui, "Pan modifier", &mut controls.keys.pan_modifier);
+    modifier_combo(ui, "Opposite view", &mut controls.keys.opposite_view);
+    ui.horizontal(|ui| {
+        ui.label("Fly look: ");
+        button_combo(ui, "fly look", &mut controls.fly.look);
+    });
+    modifier_combo(ui, "Fly fast", &mut controls.fly.fast);
+    modifier_combo(ui, "Fly slow", &mut controls.fly.slow);
+
+    for (label, value) in [
+        ("Orbit sensitivity", &mut controls.orbit_sensitivity),
+        ("Pan sensitivity", &mut controls.pan_sensitivity),
+        ("Zoom sensitivity", &mut controls.zoom_sensitivity),
+        ("Key orbit speed", &mut controls.key_orbit_speed),
+        ("Key pan speed", &mut controls.key_pan_speed),
+        ("Key zoom speed", &mut controls.key_zoom_speed),
+        ("Orbit smoothing", &mut controls.orbit_smoothing),
+        ("Pan smoothing", &mut controls.pan_smoothing),
+        ("Zoom smoothing", &mut controls.zoom_smoothing),
+        ("Momentum", &mut controls.momentum),
+        ("Fly speed", &mut controls.fly.speed),
+        ("Fly speed multiplier", &mut controls.fly.speed_multiplier),
+        ("Fly look sensitivity", &mut controls.fly.look_sensitivity),
+    ] {
+        ui.horizontal(|ui| {
+            ui.label(format!("{}: ", label));
+            ui.add(
+                egui::DragValue::new(value)
+                    .speed(0.01)
+                    .clamp_range(0.0..=f32::MAX),
+            );
+        });
+    }
+    ui.horizontal(|ui| {
+        ui.label("Invert: ");
+        ui.checkbox(&mut controls.invert_orbit_x, "Orbit X");
+        ui.checkbox(&mut controls.invert_orbit_y, "Orbit Y");
+        ui.checkbox(&mut controls.invert_pan, "Pan");
+        ui.checkbox(&mut controls.invert_zoom, "Zoom");
+    });
+
+    ui.horizontal(|ui| {
+        if ui.button("Save controls").clicked() {
+            if let Err(err) = controls.save() {
+                error!("Failed to save the camera controls: {:?}", err);
+            }
+        }
+        if ui.button("Reset to defaults").clicked() {
+            *controls = PanOrbitCameraControls::default();
+        }
+    });
+}
+
+/// Button and modifier dropdowns of each binding, with buttons to add and remove bindings
+fn mouse_bindings(ui: &mut Ui, label: &str, bindings: &mut Vec<MouseBinding>) {
+    let mut removed = None;
+    for (i, binding) in bindings.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(format!("{}: ", label));
+            button_combo(ui, (label, i), &mut binding.button);
+            egui::ComboBox::from_id_source((label, i, "modifier"))
+                .selected_text(format!("{:?}", binding.modifier))
+                .show_ui(ui, |ui| {
+                    for modifier in Modifier::ALL {
+                        ui.selectable_value(
+                            &mut binding.modifier,
+                            modifier,
+                            format!("{:?}", modifier),
+                        );
+                    }
+                });
+            if ui.small_button("-").clicked() {
+                removed = Some(i);
+            }
+        });
+    }
+    if let Some(i) = removed {
+        bindings.remove(i);
+    }
+    if ui
+        .small_button(format!("Add {} binding", label.to_lowercase()))
+        .clicked()
+    {
+        bindings.push(MouseBinding::new(MouseButton::Left, Modifier::None));
+    }
+}
+
+fn button_combo(ui: &mut Ui, id: impl std::hash::Hash, button: &mut MouseButton) {
+    egui::ComboBox::from_id_source((id, "button"))
+        .selected_text(format!("{:?}", button))
+        .show_ui(ui, |ui| {
+            for value in [MouseButton::Left, MouseButton::Right, MouseButton::Middle] {
+                ui.selectable_value(button, value, format!("{:?}", value));
+            }
+        });
+}
+
+fn modifier_combo(ui: &mut Ui, label: &str, modifier: &mut Modifier) {
+    ui.horizontal(|ui| {
+        ui.label(format!("{}: ", label));
+        egui::ComboBox::from_id_source(label)
+            .selected_text(format!("{:?}", modifier))
+            .show_ui(ui, |ui| {
+                for value in Modifier::ALL {
+                    ui.selectable_value(modifier, value, format!("{:?}", value));
+                }
+            });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orbit_round_trip() {
+        let focus = Vec3::new(1.0, -2.0, 0.5);
+        let transform = Transform::from_xyz(3.0, 3.5, 10.0).looking_at(focus, Vec3::Y);
+        let orbit = Orbit::from_transform(&transform, focus).transform();
+        assert!(orbit.translation.distance(transform.translation) < 1e-4);
+        assert!(orbit.rotation.abs_diff_eq(transform.rotation, 1e-5));
+    }
+
+    #[test]
+    fn damping_is_frame_rate_independent() {
+        let controls = PanOrbitCameraControls::default();
+        let target = Orbit {
+            focus: Vec3::ONE,
+            radius: 10.0,
+            yaw: 1.0,
+            pitch: -0.5,
+            scale: 2.0,
+        };
+        let mut slow = Orbit::default();
+        let mut fast = Orbit::default();
+        for _ in 0..10 {
+            slow.damp(&target, controls.damping(1.0 / 30.0));
+            fast.damp(&target, controls.damping(1.0 / 120.0));
+            fast.damp(&target, controls.damping(1.0 / 120.0));
+            fast.damp(&target, controls.damping(1.0 / 120.0));
+            fast.damp(&target, controls.damping(1.0 / 120.0));
+        }
+        assert!(slow.focus.distance(fast.focus) < 1e-4);
+        assert!((slow.radius - fast.radius).abs() < 1e-4);
+        assert!((slow.yaw - fast.yaw).abs() < 1e-4);
+        assert!((slow.pitch - fast.pitch).abs() < 1e-4);
+        // Without smoothing the target is reached right away
+        let mut instant = Orbit::default();
+        instant.damp(&target, Vec3::ONE);
+        assert_eq!(instant, target);
+    }
+
+    #[test]
+    fn momentum_is_kept_after_release() {
+        let drag = |controls: &PanOrbitCameraControls| {
+            let mut velocity = Vec2::ZERO;
+            for _ in 0..10 {
+                controls.apply_momentum(&mut velocity, Vec2::new(0.1, 0.0), true, 1.0 / 60.0);
+            }
+            controls.apply_momentum(&mut velocity, Vec2::ZERO, false, 1.0 / 60.0)
+        };
+        // Without momentum the camera stops on the frame the button is released
+        let controls = PanOrbitCameraControls {
+            momentum: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(drag(&controls), Vec2::ZERO);
+        let controls = PanOrbitCameraControls {
+            momentum: 0.2,
+            ..Default::default()
+        };
+        assert!(drag(&controls).x > 0.0);
+    }
+
+    #[test]
+    fn lerp_turns_the_shortest_way() {
+        let from = Orbit {
+            yaw: 3.0,
+            ..Default::default()
+        };
+        let to = Orbit {
+            yaw: -3.0,
+            radius: 7.0,
+            ..Default::default()
+        };
+        // Halfway through the 2π - 6 radians crossing ±π rather than through 0
+        let half = from.lerp(&to, 0.5);
+        assert!((half.yaw - PI).abs() < 1e-5);
+        assert!((half.radius - 6.0).abs() < 1e-6);
+        let end = from.lerp(&to, 1.0);
+        // A full turn away from `to`, at the same position
+        let (end, to) = (end.transform(), to.transform());
+        assert!(end.translation.distance(to.translation) < 1e-5);
+    }
+}
@@ -0,0 +1,527 @@
+use bevy::{
+    ecs::system::{
+        lifetimeless::{SRes, SResMut},
+        SystemParamItem,
+    },
+    pbr::{Material, MaterialPipeline},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        render_asset::{PrepareAssetError, RenderAsset},
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer,
+            BufferBindingType, BufferInitDescriptor, BufferUsages, ShaderStages,
+        },
+        renderer::RenderDevice,
+    },
+    utils::HashMap,
+};
+use bevy_egui::egui::Ui;
+use naga::{ScalarKind, StorageClass, TypeInner, VectorSize};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    inspector::{inspect_color, inspect_range, Inspect, InspectField},
+    presets::Preset,
+    shader_errors::process_shader,
+    shader_material::{ShaderMaterial, ShaderMaterialPlugin, UpdatedMaterials},
+};
+
+/// wgpu doesn't accept empty uniform buffers, used when the shader has no material uniform
+const MIN_UNIFORM_SIZE: usize = 16;
+
+/// Value of a member of the material uniform struct
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum UniformValue {
+    F32(f32),
+    I32(i32),
+    U32(u32),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4(Vec4),
+}
+
+impl UniformValue {
+    /// Zero value of a WGSL type, `None` if the type isn't supported
+    fn zero(inner: &TypeInner) -> Option<Self> {
+        match *inner {
+            TypeInner::Scalar { kind, width: 4 } => match kind {
+                ScalarKind::Float => Some(Self::F32(0.0)),
+                ScalarKind::Sint => Some(Self::I32(0)),
+                ScalarKind::Uint => Some(Self::U32(0)),
+                ScalarKind::Bool => None,
+            },
+            TypeInner::Vector {
+                size,
+                kind: ScalarKind::Float,
+                width: 4,
+            } => Some(match size {
+                VectorSize::Bi => Self::Vec2(Vec2::ZERO),
+                VectorSize::Tri => Self::Vec3(Vec3::ZERO),
+                VectorSize::Quad => Self::Vec4(Vec4::ZERO),
+            }),
+            _ => None,
+        }
+    }
+
+    fn same_type(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        match self {
+            Self::F32(value) => floats_to_bytes(&[value]),
+            Self::I32(value) => value.to_ne_bytes().to_vec(),
+            Self::U32(value) => value.to_ne_bytes().to_vec(),
+            Self::Vec2(value) => floats_to_bytes(&value.to_array()),
+            Self::Vec3(value) => floats_to_bytes(&value.to_array()),
+            Self::Vec4(value) => floats_to_bytes(&value.to_array()),
+        }
+    }
+}
+
+fn floats_to_bytes(floats: &[f32]) -> Vec<u8> {
+    floats
+        .iter()
+        .flat_map(|float| float.to_ne_bytes())
+        .collect()
+}
+
+/// Inspector hints read from the comments of a WGSL struct member
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Annotations {
+    /// `// @range(min, max)`, edits a `f32` with a slider
+    pub range: Option<(f32, f32)>,
+    /// `// @color`, edits a `vec4<f32>` with a color picker
+    pub color: bool,
+}
+
+impl Annotations {
+    fn parse_comment(&mut self, comment: &str) {
+        if comment.contains("@color") {
+            self.color = true;
+        }
+        let range = comment
+            .split_once("@range(")
+            .and_then(|(_, rest)| rest.split_once(')'))
+            .and_then(|(args, _)| args.split_once(','));
+        if let Some((min, max)) = range {
+            if let (Ok(min), Ok(max)) = (min.trim().parse(), max.trim().parse()) {
+                self.range = Some((min, max));
+            }
+        }
+    }
+
+    /// Initial value of a new member, colors start white and ranges at their minimum
+    fn initial_value(&self, zero: UniformValue) -> UniformValue {
+        match (zero, self) {
+            (UniformValue::Vec4(_), Self { color: true, .. }) => UniformValue::Vec4(Vec4::ONE),
+            (
+                UniformValue::F32(_),
+                Self {
+                    range: Some((min, _)),
+                    ..
+                },
+            ) => UniformValue::F32(*min),
+            _ => zero,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UniformField {
+    pub name: String,
+    pub value: UniformValue,
+    /// Offset of the member in the uniform buffer
+    #[serde(skip)]
+    pub offset: u32,
+    #[serde(skip)]
+    pub annotations: Annotations,
+}
+
+/// A material whose uniform is read from its shader at runtime.
+///
+/// The shader is parsed with naga to find the struct bound at `group(1) binding(0)`,
+/// every `f32`, `i32`, `u32` and `vecN<f32>` member becomes an editable field.
+/// The layout is read again every time the shader changes.
+#[derive(Debug, Clone, TypeUuid, Serialize, Deserialize)]
+#[uuid = "f4a1d7c2-5b3e-4e8a-9c61-2d7f0b9e8a53"]
+pub struct DynamicMaterial {
+    #[serde(skip)]
+    pub shader: Handle<Shader>,
+    /// Members of the uniform struct, in declaration order
+    pub fields: Vec<UniformField>,
+    /// Size of the uniform struct, `None` until the shader has been parsed
+    #[serde(skip)]
+    pub uniform_size: Option<u32>,
+}
+
+impl DynamicMaterial {
+    pub fn new(shader: Handle<Shader>) -> Self {
+        Self {
+            shader,
+            fields: Vec::new(),
+            uniform_size: None,
+        }
+    }
+
+    /// Initial values of the uniform members, applied once the shader is parsed
+    #[must_use]
+    pub fn with_values(mut self, values: impl IntoIterator<Item = (String, UniformValue)>) -> Self {
+        self.fields = values
+            .into_iter()
+            .map(|(name, value)| UniformField {
+                name,
+                value,
+                offset: 0,
+                annotations: Annotations::default(),
+            })
+            .collect();
+        self
+    }
+
+    fn value(&self, name: &str) -> Option<UniformValue> {
+        self.fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| field.value)
+    }
+
+    fn has_layout(&self, layout: &UniformLayout) -> bool {
+        self.uniform_size == Some(layout.size)
+            && self.fields.len() == layout.fields.len()
+            && self.fields.iter().zip(&layout.fields).all(|(a, b)| {
+                a.name == b.name
+                    && a.offset == b.offset
+                    && a.annotations == b.annotations
+                    && a.value.same_type(&b.value)
+            })
+    }
+
+    /// Replaces the fields with the ones of `layout`, keeping the values of the members
+    /// that still exist with the same type
+    fn set_layout(&mut self, mut layout: UniformLayout) {
+        for field in &mut layout.fields {
+            if let Some(value) = self
+                .value(&field.name)
+                .filter(|value| value.same_type(&field.value))
+            {
+                field.value = value;
+            }
+        }
+        self.fields = layout.fields;
+        self.uniform_size = Some(layout.size);
+    }
+}
+
+/// The material uniform struct of a shader
+struct UniformLayout {
+    size: u32,
+    fields: Vec<UniformField>,
+}
+
+/// Reads the layout of the struct bound at `group(1) binding(0)` in a WGSL shader
+/// whose imports have been resolved
+fn parse_uniform_layout(source: &str) -> Result<UniformLayout, String> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|err| err.emit_to_string(source))?;
+
+    let uniform = module.global_variables.iter().find(|(_, var)| {
+        var.class == StorageClass::Uniform
+            && var
+                .binding
+                .as_ref()
+                .map_or(false, |binding| binding.group == 1 && binding.binding == 0)
+    });
+    let ty = match uniform {
+        Some((_, var)) => &module.types[var.ty],
+        None => {
+            return Ok(UniformLayout {
+                size: 0,
+                fields: Vec::new(),
+            })
+        }
+    };
+    let (members, size) = match &ty.inner {
+        TypeInner::Struct { members, span } => (members, *span),
+        _ => return Err("the uniform at group(1) binding(0) must be a struct".into()),
+    };
+    let annotations = ty
+        .name
+        .as_deref()
+        .map(|name| parse_annotations(source, name))
+        .unwrap_or_default();
+
+    let fields = members
+        .iter()
+        .filter_map(|member| {
+            let name = member.name.clone()?;
+            let zero = UniformValue::zero(&module.types[member.ty].inner).or_else(|| {
+                warn!("Uniform member `{}` has an unsupported type", name);
+                None
+            })?;
+            let annotations = annotations.get(&name).cloned().unwrap_or_default();
+            Some(UniformField {
+                value: annotations.initial_value(zero),
+                name,
+                offset: member.offset,
+                annotations,
+            })
+        })
+        .collect();
+
+    Ok(UniformLayout { size, fields })
+}
+
+/// Collects the `@range` and `@color` annotations of the members of `struct_name`,
+/// from comments on the member line or on the lines right above it
+fn parse_annotations(source: &str, struct_name: &str) -> HashMap<String, Annotations> {
+    let is_declaration = |line: &str| {
+        line.trim_start()
+            .strip_prefix("struct ")
+            .map_or(false, |rest| {
+                rest.trim().trim_end_matches('{').trim() == struct_name
+            })
+    };
+
+    let mut annotations = HashMap::default();
+    let mut pending = Annotations::default();
+    for line in source
+        .lines()
+        .skip_while(|line| !is_declaration(line))
+        .skip(1)
+    {
+        let (code, comment) = match line.split_once("//") {
+            Some((code, comment)) => (code.trim(), Some(comment)),
+            None => (line.trim(), None),
+        };
+        if let Some(comment) = comment {
+            pending.parse_comment(comment);
+        }
+        if code.starts_with('}') {
+            break;
+        }
+        if let Some((name, _)) = code.split_once(':') {
+            // Skips attributes like `[[align(16)]]`
+            let name = name.rsplit(']').next().unwrap_or_default().trim();
+            annotations.insert(name.to_owned(), std::mem::take(&mut pending));
+        }
+    }
+    annotations
+}
+
+/// Reads the uniform layout of new materials, and of every material when a shader changes
+#[allow(clippy::needless_pass_by_value)]
+fn update_dynamic_materials(
+    mut shader_events: EventReader<AssetEvent<Shader>>,
+    mut material_events: EventReader<AssetEvent<DynamicMaterial>>,
+    shaders: Res<Assets<Shader>>,
+    mut materials: ResMut<Assets<DynamicMaterial>>,
+) {
+    let shaders_changed = shader_events
+        .iter()
+        .any(|event| !matches!(event, AssetEvent::Removed { .. }));
+    let created: Vec<_> = material_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Created { handle } => Some(handle.id),
+            _ => None,
+        })
+        .collect();
+    let ids: Vec<_> = if shaders_changed {
+        materials.ids().collect()
+    } else {
+        created
+    };
+
+    for id in ids {
+        let shader = match materials.get(id) {
+            Some(material) => material.shader.clone_weak(),
+            None => continue,
+        };
+        let source = match process_shader(&shader, &shaders) {
+            Some(source) => source,
+            None => continue,
+        };
+        let layout = match parse_uniform_layout(&source) {
+            Ok(layout) => layout,
+            Err(err) => {
+                warn!("Failed to read the uniform of a dynamic material:\n{}", err);
+                continue;
+            }
+        };
+        // Only mark the material as modified when its layout changed
+        if !materials.get(id).unwrap().has_layout(&layout) {
+            materials.get_mut(id).unwrap().set_layout(layout);
+        }
+    }
+}
+
+pub struct DynamicMaterialPlugin;
+
+impl Plugin for DynamicMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ShaderMaterialPlugin::<DynamicMaterial>::default())
+            .add_system(update_dynamic_materials);
+    }
+}
+
+impl Inspect for DynamicMaterial {
+    fn inspect(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = false;
+        for field in &mut self.fields {
+            let label = field.name.as_str();
+            changed |= match (&mut field.value, &field.annotations) {
+                (UniformValue::Vec4(color), Annotations { color: true, .. }) => {
+                    inspect_color(ui, label, color)
+                }
+                (
+                    UniformValue::F32(value),
+                    Annotations {
+                        range: Some((min, max)),
+                        ..
+                    },
+                ) => inspect_range(ui, label, value, *min..=*max),
+                (UniformValue::F32(value), _) => value.inspect_field(ui, label),
+                (UniformValue::I32(value), _) => value.inspect_field(ui, label),
+                (UniformValue::U32(value), _) => value.inspect_field(ui, label),
+                (UniformValue::Vec2(value), _) => value.inspect_field(ui, label),
+                (UniformValue::Vec3(value), _) => value.inspect_field(ui, label),
+                (UniformValue::Vec4(value), _) => value.inspect_field(ui, label),
+            };
+        }
+        changed
+    }
+}
+
+impl Preset for DynamicMaterial {
+    const FOLDER: &'static str = "dynamic_material";
+
+    /// Only applies the values of the members the current shader still has
+    fn apply(&mut self, preset: Self) {
+        for field in &mut self.fields {
+            if let Some(value) = preset
+                .value(&field.name)
+                .filter(|value| value.same_type(&field.value))
+            {
+                field.value = value;
+            }
+        }
+    }
+}
+
+pub struct GpuDynamicMaterial {
+    buffer: Buffer,
+    size: usize,
+    shader: Handle<Shader>,
+    bind_group: BindGroup,
+}
+
+impl ShaderMaterial for DynamicMaterial {
+    fn uniform_bytes(&self) -> Vec<u8> {
+        let size = self.uniform_size.unwrap_or_default() as usize;
+        let mut bytes = vec![0; size.max(MIN_UNIFORM_SIZE)];
+        for field in &self.fields {
+            let value = field.value.to_bytes();
+            let offset = field.offset as usize;
+            bytes[offset..offset + value.len()].copy_from_slice(&value);
+        }
+        bytes
+    }
+
+    fn textures(&self) -> Vec<Option<Handle<Image>>> {
+        Vec::new()
+    }
+
+    fn gpu_buffer(prepared: &Self::PreparedAsset) -> Option<&Buffer> {
+        Some(&prepared.buffer)
+    }
+
+    fn gpu_textures(_prepared: &Self::PreparedAsset) -> &[Option<Handle<Image>>] {
+        &[]
+    }
+
+    /// The buffer can't be reused when the shader changed the size of the uniform
+    fn can_update_in_place(
+        prepared: &Self::PreparedAsset,
+        bytes: &[u8],
+        _textures: &[Option<Handle<Image>>],
+    ) -> bool {
+        prepared.size == bytes.len()
+    }
+
+    fn gpu_shader(prepared: &Self::PreparedAsset) -> Option<&Handle<Shader>> {
+        Some(&prepared.shader)
+    }
+}
+
+impl RenderAsset for DynamicMaterial {
+    type ExtractedAsset = DynamicMaterial;
+    type PreparedAsset = GpuDynamicMaterial;
+    type Param = (
+        SRes<RenderDevice>,
+        SRes<MaterialPipeline<Self>>,
+        SResMut<UpdatedMaterials<Self>>,
+    );
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        extracted_asset: Self::ExtractedAsset,
+        (render_device, material_pipeline, updated_materials): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        // The layout isn't known until the shader is loaded
+        if extracted_asset.uniform_size.is_none() {
+            return Err(PrepareAssetError::RetryNextUpdate(extracted_asset));
+        }
+        if let Some(prepared) = updated_materials.take(&extracted_asset) {
+            return Ok(prepared);
+        }
+
+        let bytes = extracted_asset.uniform_bytes();
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            contents: &bytes,
+            label: Some("dynamic_material_buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: None,
+            layout: &material_pipeline.material_layout,
+        });
+        Ok(GpuDynamicMaterial {
+            buffer,
+            size: bytes.len(),
+            shader: extracted_asset.shader,
+            bind_group,
+        })
+    }
+}
+
+impl Material for DynamicMaterial {
+    fn bind_group(render_asset: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+        &render_asset.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX_FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    // The size depends on the shader
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: None,
+        })
+    }
+}
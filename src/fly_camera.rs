@@ -0,0 +1,181 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use bevy::{input::mouse::MouseMotion, prelude::*};
+use bevy_egui::EguiContext;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{damping_factor, Modifier, Orbit, PanOrbitCamera, PanOrbitCameraControls};
+
+/// Keeps the camera from looking straight up or down, where the yaw is undefined
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+/// Keys and speeds of the fly mode, part of the [`PanOrbitCameraControls`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlyControls {
+    /// Switches between orbiting and flying
+    pub toggle: KeyCode,
+    pub forward: KeyCode,
+    pub backward: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    /// Moves along the global Y axis
+    pub up: KeyCode,
+    pub down: KeyCode,
+    /// Looks around while held
+    pub look: MouseButton,
+    pub fast: Modifier,
+    pub slow: Modifier,
+    /// Units per second
+    pub speed: f32,
+    /// The speed is multiplied by this while `fast` is held, and divided while `slow` is
+    pub speed_multiplier: f32,
+    /// Dragging across the whole window turns the camera by this many full turns horizontally,
+    /// and half turns vertically
+    pub look_sensitivity: f32,
+}
+
+impl Default for FlyControls {
+    fn default() -> Self {
+        Self {
+            toggle: KeyCode::Tab,
+            forward: KeyCode::W,
+            backward: KeyCode::S,
+            left: KeyCode::A,
+            right: KeyCode::D,
+            up: KeyCode::E,
+            down: KeyCode::Q,
+            look: MouseButton::Right,
+            fast: Modifier::Shift,
+            slow: Modifier::Control,
+            speed: 3.0,
+            speed_multiplier: 4.0,
+            look_sensitivity: 0.5,
+        }
+    }
+}
+
+/// Present on a [`PanOrbitCamera`] in fly mode, which moves it instead of the orbit controls
+#[derive(Component)]
+pub struct FlyCamera {
+    yaw: f32,
+    pitch: f32,
+    velocity: Vec3,
+}
+
+impl FlyCamera {
+    fn new(transform: &Transform) -> Self {
+        let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        Self {
+            yaw,
+            pitch: pitch.clamp(-MAX_PITCH, MAX_PITCH),
+            velocity: Vec3::ZERO,
+        }
+    }
+
+    fn rotation(&self) -> Quat {
+        Quat::from_rotation_y(self.yaw) * Quat::from_rotation_x(self.pitch)
+    }
+}
+
+/// Switches the cameras between orbit and fly mode with the `toggle` key of their controls.
+/// Back in orbit mode, the camera orbits around the point in front of it at the distance it was
+/// orbiting from before flying.
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+pub fn toggle_fly_camera(
+    mut commands: Commands,
+    input_keys: Res<Input<KeyCode>>,
+    mut query: Query<(
+        Entity,
+        &mut PanOrbitCamera,
+        &PanOrbitCameraControls,
+        &Transform,
+        Option<&FlyCamera>,
+    )>,
+    mut egui_context: ResMut<EguiContext>,
+) {
+    if egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    for (entity, mut pan_orbit, controls, transform, fly) in query.iter_mut() {
+        if !input_keys.just_pressed(controls.fly.toggle) {
+            continue;
+        }
+        if fly.is_some() {
+            let focus = transform.translation + transform.forward() * pan_orbit.target.radius;
+            let scale = pan_orbit.target.scale;
+            pan_orbit.jump_to(Orbit {
+                scale,
+                ..Orbit::from_transform(transform, focus)
+            });
+            commands.entity(entity).remove::<FlyCamera>();
+        } else {
+            commands.entity(entity).insert(FlyCamera::new(transform));
+        }
+    }
+}
+
+/// Moves the cameras in fly mode, WASD to move, E and Q to go up and down, right click to look
+/// around by default
+#[allow(clippy::needless_pass_by_value)]
+pub fn fly_camera(
+    windows: Res<Windows>,
+    time: Res<Time>,
+    mut ev_motion: EventReader<MouseMotion>,
+    input_mouse: Res<Input<MouseButton>>,
+    input_keys: Res<Input<KeyCode>>,
+    mut query: Query<(&mut FlyCamera, &PanOrbitCameraControls, &mut Transform)>,
+    mut egui_context: ResMut<EguiContext>,
+) {
+    let ctx = egui_context.ctx_mut();
+    let use_mouse = !ctx.is_pointer_over_area();
+    let use_keys = !ctx.wants_keyboard_input();
+
+    let motion: Vec2 = ev_motion.iter().map(|ev| &ev.delta).sum();
+    let window = windows.get_primary().map_or(Vec2::ZERO, |window| {
+        Vec2::new(window.width(), window.height())
+    });
+    let delta_time = time.delta_seconds();
+
+    for (mut fly, controls, mut transform) in query.iter_mut() {
+        let fly_controls = &controls.fly;
+        if use_mouse && input_mouse.pressed(fly_controls.look) && window.x > 0.0 && window.y > 0.0 {
+            let look = motion / window * Vec2::new(PI * 2.0, PI) * fly_controls.look_sensitivity;
+            fly.yaw -= look.x;
+            fly.pitch = (fly.pitch - look.y).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+
+        let mut direction = Vec3::ZERO;
+        let mut speed = fly_controls.speed;
+        if use_keys {
+            let rotation = fly.rotation();
+            for (key, axis) in [
+                (fly_controls.forward, rotation * -Vec3::Z),
+                (fly_controls.backward, rotation * Vec3::Z),
+                (fly_controls.left, rotation * -Vec3::X),
+                (fly_controls.right, rotation * Vec3::X),
+                (fly_controls.up, Vec3::Y),
+                (fly_controls.down, -Vec3::Y),
+            ] {
+                if input_keys.pressed(key) {
+                    direction += axis;
+                }
+            }
+            if fly_controls.fast.held(&input_keys) {
+                speed *= fly_controls.speed_multiplier;
+            }
+            if fly_controls.slow.held(&input_keys) {
+                speed /= fly_controls.speed_multiplier;
+            }
+        }
+
+        // The speed changes as smoothly as the focus point when panning
+        let velocity = direction.normalize_or_zero() * speed;
+        let factor = damping_factor(controls.pan_smoothing, delta_time);
+        fly.velocity = fly.velocity.lerp(velocity, factor);
+
+        let rotation = fly.rotation();
+        transform.translation += fly.velocity * delta_time;
+        transform.rotation = rotation;
+    }
+}
@@ -0,0 +1,395 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        primitives::Aabb,
+        render_resource::PrimitiveTopology,
+    },
+};
+use bevy_egui::EguiContext;
+
+use crate::{
+    camera::{Orbit, PanOrbitCamera, PanOrbitCameraControls},
+    fly_camera::FlyCamera,
+    orthographic::OrthographicCamera,
+    Label,
+};
+
+/// Seconds the camera takes to frame the selection or move to a new pivot
+const FRAME_DURATION: f32 = 0.5;
+
+/// Room left around the framed entities, as a fraction of their size
+const FRAME_MARGIN: f32 = 1.1;
+
+/// Maximum seconds between the two clicks of a double click
+const DOUBLE_CLICK_TIME: f64 = 0.3;
+
+/// Maximum cursor distance in pixels between the two clicks of a double click
+const DOUBLE_CLICK_DISTANCE: f32 = 5.0;
+
+/// The labeled entity framed by the frame hotkey, the last one framed or double clicked
+#[derive(Default)]
+pub struct Selection(pub Option<Entity>);
+
+/// Moves the orbit cameras to fit these entities and their children in view
+pub struct FrameEntities(pub Vec<Entity>);
+
+/// Sends [`FrameEntities`] with the selection when the `frame` key is pressed,
+/// or with every labeled entity when nothing is selected
+#[allow(clippy::needless_pass_by_value)]
+fn frame_hotkey(
+    input_keys: Res<Input<KeyCode>>,
+    selection: Res<Selection>,
+    labeled: Query<Entity, With<Label>>,
+    cameras: Query<&PanOrbitCameraControls>,
+    mut frame_events: EventWriter<FrameEntities>,
+    mut egui_context: ResMut<EguiContext>,
+) {
+    if egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    if !cameras
+        .iter()
+        .any(|controls| input_keys.just_pressed(controls.keys.frame))
+    {
+        return;
+    }
+    let entities = match selection.0 {
+        Some(entity) if labeled.get(entity).is_ok() => vec![entity],
+        _ => labeled.iter().collect(),
+    };
+    frame_events.send(FrameEntities(entities));
+}
+
+/// World space bounding box of the meshes of an entity and its descendants
+fn world_bounds(
+    entity: Entity,
+    bounds: &Query<(Option<&Aabb>, &GlobalTransform, Option<&Children>)>,
+    min: &mut Vec3,
+    max: &mut Vec3,
+) {
+    let (aabb, transform, children) = match bounds.get(entity) {
+        Ok(bounds) => bounds,
+        Err(_) => return,
+    };
+    if let Some(aabb) = aabb {
+        let matrix = transform.compute_matrix();
+        let (center, half_extents) = (Vec3::from(aabb.center), Vec3::from(aabb.half_extents));
+        for corner in 0..8 {
+            let sign = Vec3::new(
+                if corner & 1 == 0 { -1.0 } else { 1.0 },
+                if corner & 2 == 0 { -1.0 } else { 1.0 },
+                if corner & 4 == 0 { -1.0 } else { 1.0 },
+            );
+            let point = matrix.transform_point3(center + half_extents * sign);
+            *min = min.min(point);
+            *max = max.max(point);
+        }
+    }
+    for child in children.into_iter().flat_map(|children| children.iter()) {
+        world_bounds(*child, bounds, min, max);
+    }
+}
+
+/// Distance from which a sphere of `radius` fits in the view of a perspective projection
+fn fit_distance(radius: f32, projection: &PerspectiveProjection) -> f32 {
+    let half_fov_y = projection.fov * 0.5;
+    let half_fov_x = (half_fov_y.tan() * projection.aspect_ratio).atan();
+    radius * FRAME_MARGIN / half_fov_x.min(half_fov_y).min(FRAC_PI_2).sin()
+}
+
+/// Scale at which a sphere of `radius` fits in the view of an orthographic projection
+fn fit_scale(radius: f32, projection: &OrthographicProjection) -> f32 {
+    let width = projection.right - projection.left;
+    let height = projection.top - projection.bottom;
+    radius * FRAME_MARGIN * 2.0 / width.min(height)
+}
+
+/// Animates the orbit cameras to look at the bounding sphere of the entities from the distance,
+/// or with the orthographic scale, fitting it in view
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn frame_entities(
+    mut frame_events: EventReader<FrameEntities>,
+    mut selection: ResMut<Selection>,
+    bounds: Query<(Option<&Aabb>, &GlobalTransform, Option<&Children>)>,
+    mut cameras: Query<
+        (
+            &mut PanOrbitCamera,
+            Option<&PerspectiveProjection>,
+            Option<&OrthographicProjection>,
+        ),
+        Without<FlyCamera>,
+    >,
+) {
+    for FrameEntities(entities) in frame_events.iter() {
+        let (mut min, mut max) = (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY));
+        for entity in entities {
+            world_bounds(*entity, &bounds, &mut min, &mut max);
+        }
+        if !min.cmple(max).all() {
+            // Nothing has been loaded yet
+            continue;
+        }
+        if let [entity] = entities[..] {
+            selection.0 = Some(entity);
+        }
+
+        let center = (min + max) * 0.5;
+        let radius = ((max - min).length() * 0.5).max(0.01);
+        for (mut pan_orbit, perspective, orthographic) in cameras.iter_mut() {
+            let orbit = if let Some(orthographic) = orthographic {
+                // Backing out of the sphere, which would be cut by the near plane
+                Orbit {
+                    focus: center,
+                    radius: pan_orbit.target.radius.max(radius * FRAME_MARGIN),
+                    scale: fit_scale(radius, orthographic),
+                    ..pan_orbit.target
+                }
+            } else if let Some(perspective) = perspective {
+                Orbit {
+                    focus: center,
+                    radius: fit_distance(radius, perspective),
+                    ..pan_orbit.target
+                }
+            } else {
+                continue;
+            };
+            pan_orbit.animate_to(orbit, FRAME_DURATION);
+        }
+    }
+}
+
+/// Distance along the ray to the triangle, Möller–Trumbore intersection seeing both faces
+fn ray_triangle(origin: Vec3, direction: Vec3, corners: [Vec3; 3]) -> Option<f32> {
+    let (edge_1, edge_2) = (corners[1] - corners[0], corners[2] - corners[0]);
+    let cross_2 = direction.cross(edge_2);
+    let determinant = edge_1.dot(cross_2);
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+    // Barycentric coordinates of the intersection with the plane of the triangle
+    let offset = origin - corners[0];
+    let u = offset.dot(cross_2) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let cross_1 = offset.cross(edge_1);
+    let v = direction.dot(cross_1) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = edge_2.dot(cross_1) * inverse_determinant;
+    if distance > 0.0 {
+        Some(distance)
+    } else {
+        None
+    }
+}
+
+/// Distance along the ray to the closest triangle of a triangle list mesh, in units of
+/// `direction`. The ray is in world space and the mesh transformed by `transform`.
+fn ray_mesh(origin: Vec3, direction: Vec3, mesh: &Mesh, transform: Mat4) -> Option<f32> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return None;
+    }
+    let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float32x3(positions)) => positions,
+        _ => return None,
+    };
+    let indices: Vec<usize> = match mesh.indices() {
+        Some(Indices::U16(indices)) => indices.iter().map(|&i| usize::from(i)).collect(),
+        Some(Indices::U32(indices)) => indices.iter().map(|&i| i as usize).collect(),
+        None => (0..positions.len()).collect(),
+    };
+
+    // Intersecting in the space of the mesh keeps the distances along the world ray
+    let inverse = transform.inverse();
+    let local_origin = inverse.transform_point3(origin);
+    let local_direction = inverse.transform_vector3(direction);
+    indices
+        .chunks_exact(3)
+        .filter_map(|triangle| {
+            let corner = |k: usize| positions.get(triangle[k]).copied().map(Vec3::from);
+            let corners = [corner(0)?, corner(1)?, corner(2)?];
+            ray_triangle(local_origin, local_direction, corners)
+        })
+        .reduce(f32::min)
+}
+
+/// Ray from the camera through a point of the window in normalized device coordinates
+fn camera_ray(camera: &Camera, transform: &GlobalTransform, ndc: Vec2) -> (Vec3, Vec3) {
+    let world_from_ndc = transform.compute_matrix() * camera.projection_matrix.inverse();
+    // The depth is reversed, 1 is the near plane. 0 is the infinitely far plane of perspective
+    // projections so the second point is taken in between
+    let near = world_from_ndc.project_point3(ndc.extend(1.0));
+    let far = world_from_ndc.project_point3(ndc.extend(0.5));
+    (near, (far - near).normalize())
+}
+
+/// The labeled entity an entity is part of, itself or the model it is a mesh of
+fn labeled_ancestor(
+    mut entity: Entity,
+    parents: &Query<&Parent>,
+    labeled: &Query<(), With<Label>>,
+) -> Option<Entity> {
+    loop {
+        if labeled.get(entity).is_ok() {
+            return Some(entity);
+        }
+        entity = parents.get(entity).ok()?.0;
+    }
+}
+
+/// Double clicking a mesh makes the point clicked the focus of the orbit cameras, keeping them in
+/// place in perspective projection and panning to it in orthographic projection, and selects its
+/// labeled entity
+#[allow(clippy::needless_pass_by_value, clippy::too_many_arguments)]
+fn pick_pivot(
+    mut last_click: Local<Option<(f64, Vec2)>>,
+    windows: Res<Windows>,
+    time: Res<Time>,
+    input_mouse: Res<Input<MouseButton>>,
+    meshes: Res<Assets<Mesh>>,
+    mut selection: ResMut<Selection>,
+    mut cameras: Query<
+        (
+            &Camera,
+            &GlobalTransform,
+            &mut PanOrbitCamera,
+            Option<&OrthographicCamera>,
+        ),
+        Without<FlyCamera>,
+    >,
+    pickable: Query<(Entity, &Handle<Mesh>, &GlobalTransform, &ComputedVisibility)>,
+    parents: Query<&Parent>,
+    labeled: Query<(), With<Label>>,
+    mut egui_context: ResMut<EguiContext>,
+) {
+    if !input_mouse.just_pressed(MouseButton::Left) || egui_context.ctx_mut().is_pointer_over_area()
+    {
+        return;
+    }
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let cursor = match window.cursor_position() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    let now = time.seconds_since_startup();
+    let double_click = matches!(*last_click, Some((time, position))
+        if now - time < DOUBLE_CLICK_TIME && position.distance(cursor) < DOUBLE_CLICK_DISTANCE);
+    if !double_click {
+        *last_click = Some((now, cursor));
+        return;
+    }
+    *last_click = None;
+
+    // The cursor position origin is the bottom left corner, like normalized device coordinates
+    let ndc = cursor / Vec2::new(window.width(), window.height()) * 2.0 - Vec2::ONE;
+    for (camera, camera_transform, mut pan_orbit, orthographic) in cameras.iter_mut() {
+        let (origin, direction) = camera_ray(camera, camera_transform, ndc);
+        let hit = pickable
+            .iter()
+            .filter(|(_, _, _, visibility)| visibility.is_visible)
+            .filter_map(|(entity, handle, transform, _)| {
+                let mesh = meshes.get(handle)?;
+                let distance = ray_mesh(origin, direction, mesh, transform.compute_matrix())?;
+                Some((entity, distance))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        let (entity, distance) = match hit {
+            Some(hit) => hit,
+            None => continue,
+        };
+
+        let pivot = origin + direction * distance;
+        let orbit = if orthographic.is_some() {
+            Orbit {
+                focus: pivot,
+                ..pan_orbit.target
+            }
+        } else {
+            let eye = camera_transform.translation;
+            let transform = Transform::from_translation(eye).looking_at(pivot, Vec3::Y);
+            Orbit {
+                scale: pan_orbit.target.scale,
+                ..Orbit::from_transform(&transform, pivot)
+            }
+        };
+        pan_orbit.animate_to(orbit, FRAME_DURATION);
+        selection.0 = labeled_ancestor(entity, &parents, &labeled);
+    }
+}
+
+pub struct FramingPlugin;
+
+impl Plugin for FramingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Selection>()
+            .add_event::<FrameEntities>()
+            .add_system(frame_hotkey)
+            .add_system(frame_entities.after(frame_hotkey))
+            .add_system(pick_pivot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::camera::{CameraProjection, ScalingMode};
+
+    use super::*;
+    use crate::shapes::Grid;
+
+    #[test]
+    fn ray_hits_closest_triangle() {
+        let triangle = [Vec3::ZERO, Vec3::X, Vec3::Y];
+        let hit = ray_triangle(Vec3::new(0.25, 0.25, 2.0), -Vec3::Z, triangle);
+        assert!(matches!(hit, Some(distance) if (distance - 2.0).abs() < 1e-6));
+        // Both faces are hit, but not behind the origin or outside the triangle
+        assert!(ray_triangle(Vec3::new(0.25, 0.25, -2.0), Vec3::Z, triangle).is_some());
+        assert!(ray_triangle(Vec3::new(0.25, 0.25, 2.0), Vec3::Z, triangle).is_none());
+        assert!(ray_triangle(Vec3::new(0.75, 0.75, 2.0), -Vec3::Z, triangle).is_none());
+    }
+
+    #[test]
+    fn ray_hits_transformed_mesh() {
+        let mesh = Mesh::from(Grid::default());
+        let transform = Transform {
+            translation: Vec3::new(1.0, 2.0, 3.0),
+            rotation: Quat::IDENTITY,
+            scale: Vec3::splat(4.0),
+        };
+        let origin = Vec3::new(1.5, 10.0, 3.5);
+        let distance = ray_mesh(origin, -Vec3::Y, &mesh, transform.compute_matrix());
+        assert!(matches!(distance, Some(distance) if (distance - 8.0).abs() < 1e-5));
+        // Outside of the scaled grid
+        let origin = Vec3::new(3.5, 10.0, 3.0);
+        assert!(ray_mesh(origin, -Vec3::Y, &mesh, transform.compute_matrix()).is_none());
+    }
+
+    #[test]
+    fn framed_sphere_fits_in_view() {
+        let projection = PerspectiveProjection {
+            fov: 1.0,
+            aspect_ratio: 0.5,
+            ..Default::default()
+        };
+        let distance = fit_distance(1.0, &projection);
+        // The narrower horizontal field of view limits the distance
+        let half_fov_x = ((projection.fov * 0.5).tan() * projection.aspect_ratio).atan();
+        assert!((half_fov_x.sin() * distance - FRAME_MARGIN).abs() < 1e-5);
+
+        let mut projection = OrthographicProjection {
+            scaling_mode: ScalingMode::FixedVertical,
+            ..Default::default()
+        };
+        projection.update(1000.0, 500.0);
+        // Half the height of the view, limited by the window height
+        assert!((fit_scale(1.0, &projection) - FRAME_MARGIN).abs() < 1e-6);
+    }
+}
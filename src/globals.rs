@@ -0,0 +1,204 @@
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    prelude::*,
+    render::{
+        render_phase::{EntityRenderCommand, RenderCommandResult, TrackedRenderPass},
+        render_resource::{
+            std140::{AsStd140, Std140},
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer,
+            BufferBindingType, BufferDescriptor, BufferSize, BufferUsages, ShaderStages,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        RenderApp, RenderStage,
+    },
+};
+use bevy_egui::egui::{self, Ui};
+
+/// Delta time used when stepping a paused clock
+const STEP_DELTA: f32 = 1.0 / 60.0;
+
+/// Playground wide values every shader can read at `group(3)`, like Shadertoy's `iTime` and `iMouse`.
+/// The matching WGSL struct is in `shaders/globals.wgsl`, import it with `#import playground::globals`
+#[derive(Debug, Clone, Default, AsStd140)]
+pub struct Globals {
+    /// Size of the primary window in physical pixels
+    pub resolution: Vec2,
+    /// Cursor position in physical pixels, the origin is the bottom left corner
+    pub cursor: Vec2,
+    /// Elapsed time in seconds
+    pub time: f32,
+    pub delta_time: f32,
+    pub frame: u32,
+    /// Bit mask of the pressed mouse buttons, 1 = left, 2 = right, 4 = middle
+    pub mouse_buttons: u32,
+}
+
+/// Controls how [`Globals::time`] advances
+#[derive(Default)]
+pub struct GlobalsClock {
+    pub paused: bool,
+    /// Advance a single frame while paused
+    pub step: bool,
+}
+
+/// Keeps `shaders/globals.wgsl` loaded so it can be imported by the material shaders
+struct GlobalsShader(#[allow(dead_code)] Handle<Shader>);
+
+pub struct GlobalsPlugin;
+
+impl Plugin for GlobalsPlugin {
+    fn build(&self, app: &mut App) {
+        let shader = app
+            .world
+            .resource::<AssetServer>()
+            .load("shaders/globals.wgsl");
+        app.insert_resource(GlobalsShader(shader))
+            .init_resource::<Globals>()
+            .init_resource::<GlobalsClock>()
+            .add_system(update_globals);
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<Globals>()
+                .init_resource::<GlobalsMeta>()
+                .add_system_to_stage(RenderStage::Extract, extract_globals)
+                .add_system_to_stage(RenderStage::Prepare, prepare_globals);
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn update_globals(
+    time: Res<Time>,
+    windows: Res<Windows>,
+    input_mouse: Res<Input<MouseButton>>,
+    mut globals: ResMut<Globals>,
+    mut clock: ResMut<GlobalsClock>,
+) {
+    globals.delta_time = if clock.step {
+        STEP_DELTA
+    } else if clock.paused {
+        0.0
+    } else {
+        time.delta_seconds()
+    };
+    if !clock.paused || clock.step {
+        globals.time += globals.delta_time;
+        globals.frame = globals.frame.wrapping_add(1);
+        clock.step = false;
+    }
+
+    if let Some(window) = windows.get_primary() {
+        let scale_factor = window.scale_factor() as f32;
+        globals.resolution = Vec2::new(
+            window.physical_width() as f32,
+            window.physical_height() as f32,
+        );
+        if let Some(cursor) = window.cursor_position() {
+            globals.cursor = cursor * scale_factor;
+        }
+    }
+
+    globals.mouse_buttons = [MouseButton::Left, MouseButton::Right, MouseButton::Middle]
+        .iter()
+        .enumerate()
+        .filter(|(_, button)| input_mouse.pressed(**button))
+        .fold(0, |mask, (i, _)| mask | (1 << i));
+}
+
+pub fn inspector(ui: &mut Ui, globals: &mut Globals, clock: &mut GlobalsClock) {
+    ui.horizontal(|ui| {
+        let label = if clock.paused { "Play" } else { "Pause" };
+        if ui.button(label).clicked() {
+            clock.paused = !clock.paused;
+        }
+        if ui
+            .add_enabled(clock.paused, egui::Button::new("Step"))
+            .clicked()
+        {
+            clock.step = true;
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Time: ");
+        ui.add(
+            egui::DragValue::new(&mut globals.time)
+                .speed(0.01)
+                .clamp_range(0.0..=f32::MAX),
+        );
+    });
+    ui.label(format!("Frame: {}", globals.frame));
+}
+
+/// The GPU side of [`Globals`]
+pub struct GlobalsMeta {
+    buffer: Buffer,
+    bind_group: BindGroup,
+    pub layout: BindGroupLayout,
+}
+
+impl FromWorld for GlobalsMeta {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX_FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(Globals::std140_size_static() as u64),
+                },
+                count: None,
+            }],
+            label: Some("globals_layout"),
+        });
+        let buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("globals_buffer"),
+            size: Globals::std140_size_static() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("globals_bind_group"),
+            layout: &layout,
+        });
+
+        GlobalsMeta {
+            buffer,
+            bind_group,
+            layout,
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn extract_globals(mut commands: Commands, globals: Res<Globals>) {
+    commands.insert_resource(globals.clone());
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn prepare_globals(globals: Res<Globals>, meta: Res<GlobalsMeta>, render_queue: Res<RenderQueue>) {
+    render_queue.write_buffer(&meta.buffer, 0, globals.as_std140().as_bytes());
+}
+
+pub struct SetGlobalsBindGroup<const I: usize>;
+
+impl<const I: usize> EntityRenderCommand for SetGlobalsBindGroup<I> {
+    type Param = SRes<GlobalsMeta>;
+
+    fn render<'w>(
+        _view: Entity,
+        _item: Entity,
+        meta: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        pass.set_bind_group(I, &meta.into_inner().bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
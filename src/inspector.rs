@@ -1,71 +1,324 @@
-use bevy::prelude::*;
+use std::{any::type_name, ops::RangeInclusive};
+
+use bevy::{ecs::event::Events, prelude::*, utils::HashMap};
 use bevy_egui::{
-    egui::{self, Ui},
+    egui::{self, CollapsingHeader, Ui},
     EguiContext,
 };
 
 use crate::{
-    custom_material::{self, CustomMaterial},
-    gradient::{self, GradientMaterial},
+    camera::{self, PanOrbitCameraControls, RebindingKey},
+    framing::FrameEntities,
+    globals::{self, Globals, GlobalsClock},
+    mesh_export::{ExportMesh, MeshFormat},
+    models::{self, Model},
+    presets::{self, Preset},
+    shader_editor::ShaderEditor,
+    shader_material::ShaderMaterial,
     Label,
 };
 
-pub fn inspector_panel(
-    mut egui_context: ResMut<EguiContext>,
-    mut color_materials_query: Query<(&Label, &Handle<CustomMaterial>)>,
-    mut gradient_materials_query: Query<(&Label, &Handle<GradientMaterial>, &mut Transform)>,
-    mut custom_materials: ResMut<Assets<CustomMaterial>>,
-    mut gradient_materials: ResMut<Assets<GradientMaterial>>,
-) {
-    egui::panel::SidePanel::new(egui::panel::Side::Left, "side_panel").show(
-        egui_context.ctx_mut(),
-        |ui| {
+/// Draws the editable fields of a material, implemented by `#[derive(ShaderMaterial)]`.
+/// Returns whether a field was changed.
+pub trait Inspect {
+    fn inspect(&mut self, ui: &mut Ui) -> bool;
+}
+
+/// How a material field without an `#[inspect]` attribute is edited
+pub trait InspectField {
+    fn inspect_field(&mut self, ui: &mut Ui, label: &str) -> bool;
+}
+
+impl InspectField for f32 {
+    fn inspect_field(&mut self, ui: &mut Ui, label: &str) -> bool {
+        ui.horizontal(|ui| {
+            ui.label(format!("{}: ", label));
+            ui.add(egui::DragValue::new(self).speed(0.01)).changed()
+        })
+        .inner
+    }
+}
+
+impl InspectField for u32 {
+    fn inspect_field(&mut self, ui: &mut Ui, label: &str) -> bool {
+        ui.horizontal(|ui| {
+            ui.label(format!("{}: ", label));
+            ui.add(egui::DragValue::new(self)).changed()
+        })
+        .inner
+    }
+}
+
+impl InspectField for i32 {
+    fn inspect_field(&mut self, ui: &mut Ui, label: &str) -> bool {
+        ui.horizontal(|ui| {
+            ui.label(format!("{}: ", label));
+            ui.add(egui::DragValue::new(self)).changed()
+        })
+        .inner
+    }
+}
+
+impl InspectField for Vec2 {
+    fn inspect_field(&mut self, ui: &mut Ui, label: &str) -> bool {
+        ui.label(label);
+        let mut components = self.to_array();
+        let changed = inspect_components(ui, &mut components, 0.01);
+        *self = Vec2::from(components);
+        changed
+    }
+}
+
+impl InspectField for Vec3 {
+    fn inspect_field(&mut self, ui: &mut Ui, label: &str) -> bool {
+        ui.label(label);
+        inspect_vec3(ui, self, 0.01)
+    }
+}
+
+impl InspectField for Vec4 {
+    fn inspect_field(&mut self, ui: &mut Ui, label: &str) -> bool {
+        ui.label(label);
+        let mut components = self.to_array();
+        let changed = inspect_components(ui, &mut components, 0.01);
+        *self = Vec4::from(components);
+        changed
+    }
+}
+
+/// Draws the inspector of every entity using one material type
+type MaterialInspector = fn(&mut World, &mut Ui);
+
+/// Material types shown in the inspector panel, `ShaderMaterialPlugin` registers its material
+#[derive(Default)]
+pub struct InspectorRegistry {
+    materials: Vec<(&'static str, MaterialInspector)>,
+}
+
+impl InspectorRegistry {
+    pub fn register<M: ShaderMaterial + Preset>(&mut self) {
+        let name = type_name::<M>().rsplit("::").next().unwrap_or_default();
+        self.materials.push((name, inspect_materials::<M>));
+    }
+}
+
+/// Preset name being edited for each entity
+#[derive(Default)]
+struct PresetNames(HashMap<Entity, String>);
+
+pub fn inspector_panel(world: &mut World) {
+    let ctx = world.resource_mut::<EguiContext>().ctx_mut().clone();
+    world.init_resource::<PresetNames>();
+    world.init_resource::<RebindingKey>();
+    world.resource_scope(|world, registry: Mut<InspectorRegistry>| {
+        egui::panel::SidePanel::new(egui::panel::Side::Left, "side_panel").show(&ctx, |ui| {
             egui::ScrollArea::vertical().show(ui, |ui| {
                 ui.heading("Inspector");
-                ui.separator();
-                ui.label("Custom Materials");
-                for (label, mat) in color_materials_query.iter_mut() {
-                    if let Some(mat) = custom_materials.get_mut(mat) {
-                        custom_material::inspector(ui, label, mat);
-                    }
+                if let Some(mut editor) = world.get_resource_mut::<ShaderEditor>() {
+                    ui.checkbox(&mut editor.open, "Shader editor");
                 }
                 ui.separator();
-                ui.label("Gradient Materials");
-                for (label, mat, mut transform) in gradient_materials_query.iter_mut() {
-                    if let Some(mat) = gradient_materials.get_mut(mat) {
-                        gradient::inspector(ui, label, mat, &mut transform);
-                    }
+                ui.label("Globals");
+                world.resource_scope(|world, mut globals: Mut<Globals>| {
+                    let mut clock = world.resource_mut::<GlobalsClock>();
+                    globals::inspector(ui, &mut globals, &mut clock);
+                });
+                ui.separator();
+                CollapsingHeader::new("Camera controls").show(ui, |ui| {
+                    inspect_camera_controls(world, ui);
+                });
+                for (name, inspect) in &registry.materials {
+                    ui.separator();
+                    ui.label(*name);
+                    inspect(world, ui);
                 }
             });
-        },
-    );
+        });
+    });
 }
 
-pub fn inspect_transform(ui: &mut Ui, transform: &mut Transform) {
+fn inspect_camera_controls(world: &mut World, ui: &mut Ui) {
+    let pressed_key = world
+        .resource::<Input<KeyCode>>()
+        .get_just_pressed()
+        .next()
+        .copied();
+    world.resource_scope(|world, mut rebinding: Mut<RebindingKey>| {
+        let mut query = world.query::<&mut PanOrbitCameraControls>();
+        for mut controls in query.iter_mut(world) {
+            camera::inspector(ui, &mut controls, &mut rebinding, pressed_key);
+        }
+    });
+}
+
+fn inspect_materials<M: ShaderMaterial + Preset>(world: &mut World, ui: &mut Ui) {
+    let mut query = world.query::<(
+        Entity,
+        &Label,
+        &Handle<M>,
+        &mut Transform,
+        Option<&mut Model>,
+    )>();
+    let mut exports = Vec::new();
+    let mut frames = Vec::new();
+    world.resource_scope(|world, mut materials: Mut<Assets<M>>| {
+        world.resource_scope(|world, mut preset_names: Mut<PresetNames>| {
+            for (entity, label, handle, mut transform, model) in query.iter_mut(world) {
+                // The widgets edit copies, written back only when changed since `get_mut` marks
+                // the material as modified and its uniform is uploaded again
+                let mut material = match materials.get(handle) {
+                    Some(material) => material.clone(),
+                    None => continue,
+                };
+                let mut edited_transform = *transform;
+                let mut changed = false;
+                CollapsingHeader::new(label.0.as_str())
+                    .id_source(entity)
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        if ui.button("Frame").clicked() {
+                            frames.push(FrameEntities(vec![entity]));
+                        }
+                        if let Some(mut model) = model {
+                            if let Some(path) = models::inspector(ui, entity, &model) {
+                                model.path = path;
+                            }
+                        }
+                        changed |= material.inspect(ui);
+                        CollapsingHeader::new("Transform")
+                            .id_source((entity, "transform"))
+                            .show(ui, |ui| inspect_transform(ui, &mut edited_transform));
+                        let name = preset_names.0.entry(entity).or_default();
+                        changed |= presets::inspector(
+                            ui,
+                            entity,
+                            name,
+                            &mut material,
+                            &mut edited_transform,
+                        );
+                        ui.horizontal(|ui| {
+                            ui.label("Export mesh: ");
+                            for format in MeshFormat::ALL {
+                                let text = format.extension().to_uppercase();
+                                if ui.button(text).clicked() {
+                                    exports.push(ExportMesh { entity, format });
+                                }
+                            }
+                        });
+                    });
+                if changed {
+                    *materials.get_mut(handle).unwrap() = material;
+                }
+                if edited_transform != *transform {
+                    *transform = edited_transform;
+                }
+            }
+        });
+    });
+    if let Some(mut events) = world.get_resource_mut::<Events<ExportMesh>>() {
+        for export in exports {
+            events.send(export);
+        }
+    }
+    if let Some(mut events) = world.get_resource_mut::<Events<FrameEntities>>() {
+        for frame in frames {
+            events.send(frame);
+        }
+    }
+}
+
+pub fn inspect_color(ui: &mut Ui, label: &str, color: &mut Vec4) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(format!("{}: ", label));
+        let mut rgba = color.to_array();
+        let changed = ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed();
+        *color = Vec4::from(rgba);
+        changed
+    })
+    .inner
+}
+
+pub fn inspect_range(
+    ui: &mut Ui,
+    label: &str,
+    value: &mut f32,
+    range: RangeInclusive<f32>,
+) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(format!("{}: ", label));
+        ui.add(egui::Slider::new(value, range)).changed()
+    })
+    .inner
+}
+
+pub fn inspect_transform(ui: &mut Ui, transform: &mut Transform) -> bool {
     ui.label("Translation");
-    inspect_vec3(ui, &mut transform.translation, 0.1);
+    let mut changed = inspect_vec3(ui, &mut transform.translation, 0.1);
 
     ui.label("Rotation");
     let mut rot = Vec3::from(transform.rotation.to_euler(EulerRot::XYZ));
     rot.x = rot.x.to_degrees();
     rot.y = rot.y.to_degrees();
     rot.z = rot.z.to_degrees();
-    inspect_vec3(ui, &mut rot, 1.0);
-    transform.rotation = Quat::from_euler(
-        EulerRot::XYZ,
-        rot.x.to_radians(),
-        rot.y.to_radians(),
-        rot.z.to_radians(),
-    );
+    // The rotation is only set when edited, the Euler angles don't round trip exactly
+    if inspect_vec3(ui, &mut rot, 1.0) {
+        transform.rotation = Quat::from_euler(
+            EulerRot::XYZ,
+            rot.x.to_radians(),
+            rot.y.to_radians(),
+            rot.z.to_radians(),
+        );
+        changed = true;
+    }
+    changed
 }
 
-pub fn inspect_vec3(ui: &mut Ui, vec: &mut Vec3, speed: f32) {
+pub fn inspect_vec3(ui: &mut Ui, vec: &mut Vec3, speed: f32) -> bool {
+    let mut components = vec.to_array();
+    let changed = inspect_components(ui, &mut components, speed);
+    *vec = Vec3::from(components);
+    changed
+}
+
+fn inspect_components(ui: &mut Ui, components: &mut [f32], speed: f32) -> bool {
     ui.horizontal(|ui| {
-        ui.label("X: ");
-        ui.add(egui::DragValue::new(&mut vec.x).speed(speed));
-        ui.label("Y: ");
-        ui.add(egui::DragValue::new(&mut vec.y).speed(speed));
-        ui.label("Z: ");
-        ui.add(egui::DragValue::new(&mut vec.z).speed(speed));
-    });
+        let mut changed = false;
+        for (name, component) in ["X: ", "Y: ", "Z: ", "W: "].iter().zip(components) {
+            ui.label(*name);
+            changed |= ui
+                .add(egui::DragValue::new(component).speed(speed))
+                .changed();
+        }
+        changed
+    })
+    .inner
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy_egui::egui::{CentralPanel, Context, RawInput};
+
+    use super::*;
+    use crate::custom_material::CustomMaterial;
+
+    #[test]
+    fn untouched_widgets_report_no_change() {
+        let mut material = CustomMaterial {
+            color: Vec4::ONE,
+            scale: 1.0,
+            offset: 0.0,
+        };
+        let rotation = Quat::from_rotation_y(1.0);
+        let mut transform = Transform::from_rotation(rotation);
+        let ctx = Context::default();
+        for _ in 0..2 {
+            let _ = ctx.run(RawInput::default(), |ctx| {
+                CentralPanel::default().show(ctx, |ui| {
+                    assert!(!material.inspect(ui));
+                    assert!(!inspect_transform(ui, &mut transform));
+                });
+            });
+        }
+        assert_eq!(transform.rotation, rotation);
+    }
+}
@@ -0,0 +1,357 @@
+use std::{any::type_name, fmt, fmt::Write as _, fs};
+
+use bevy::{
+    asset::FileAssetIo,
+    math::{IVec2, IVec3, IVec4, Mat2, Mat3, Mat4, UVec2, UVec3, UVec4, Vec2, Vec3, Vec4},
+};
+use naga::{ScalarKind, TypeInner};
+
+use crate::shader_material::ShaderMaterial;
+
+/// WGSL spelling of a type used in a material uniform
+pub trait WgslType {
+    const WGSL: &'static str;
+}
+
+macro_rules! wgsl_types {
+    ($($ty:ty => $wgsl:literal),* $(,)?) => {
+        $(impl WgslType for $ty {
+            const WGSL: &'static str = $wgsl;
+        })*
+    };
+}
+
+wgsl_types! {
+    f32 => "f32",
+    i32 => "i32",
+    u32 => "u32",
+    Vec2 => "vec2<f32>",
+    Vec3 => "vec3<f32>",
+    Vec4 => "vec4<f32>",
+    IVec2 => "vec2<i32>",
+    IVec3 => "vec3<i32>",
+    IVec4 => "vec4<i32>",
+    UVec2 => "vec2<u32>",
+    UVec3 => "vec3<u32>",
+    UVec4 => "vec4<u32>",
+    Mat2 => "mat2x2<f32>",
+    Mat3 => "mat3x3<f32>",
+    Mat4 => "mat4x4<f32>",
+}
+
+/// A member of a uniform struct
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutMember {
+    pub name: String,
+    /// WGSL spelling of the type
+    pub ty: String,
+    /// std140 offset in bytes
+    pub offset: u32,
+}
+
+impl fmt::Display for LayoutMember {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} @ {}", self.name, self.ty, self.offset)
+    }
+}
+
+/// The uniform of a material as declared in Rust, generated by `#[derive(ShaderMaterial)]`
+pub struct MaterialLayout {
+    /// Asset path of the shader
+    pub shader: &'static str,
+    pub members: Vec<LayoutMember>,
+}
+
+/// Compares the uniform layout of `M` with the struct bound at `group(1) binding(0)`
+/// in its shader. Returns a readable diff when they don't match.
+pub fn check<M: ShaderMaterial>() -> Result<(), String> {
+    let layout = match M::uniform_layout() {
+        Some(layout) => layout,
+        None => return Ok(()),
+    };
+    let path = FileAssetIo::get_root_path()
+        .join("assets")
+        .join(layout.shader);
+    let source = fs::read_to_string(&path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    compare(type_name::<M>(), &layout, &source)
+}
+
+fn compare(material: &str, layout: &MaterialLayout, source: &str) -> Result<(), String> {
+    let wgsl = wgsl_layout(source)
+        .map_err(|err| format!("Failed to read the uniform of {}:\n{}", layout.shader, err))?;
+    if wgsl == layout.members {
+        return Ok(());
+    }
+
+    let mut diff = format!(
+        "The uniform layout of {} doesn't match {}:\n  {:<32}{}\n",
+        material, layout.shader, "Rust", "WGSL"
+    );
+    let describe = |member: Option<&LayoutMember>| {
+        member.map_or_else(|| "(missing)".to_owned(), ToString::to_string)
+    };
+    for i in 0..layout.members.len().max(wgsl.len()) {
+        let (rust, wgsl) = (layout.members.get(i), wgsl.get(i));
+        let marker = if rust == wgsl { ' ' } else { '!' };
+        writeln!(diff, "{} {:<32}{}", marker, describe(rust), describe(wgsl)).unwrap();
+    }
+    Err(diff)
+}
+
+/// Reads the members of the struct bound at `group(1) binding(0)`, empty if there is none.
+///
+/// Only that struct is given to naga, so the `#import`s of the shader don't need to be resolved.
+fn wgsl_layout(source: &str) -> Result<Vec<LayoutMember>, String> {
+    let struct_name = match uniform_struct_name(source) {
+        Some(name) => name,
+        None => return Ok(Vec::new()),
+    };
+    let declaration = struct_declaration(source, &struct_name)
+        .ok_or_else(|| format!("struct {} isn't declared in the shader", struct_name))?;
+    let snippet = format!(
+        "{}\n[[group(1), binding(0)]]\nvar<uniform> material: {};\n",
+        declaration, struct_name
+    );
+    let module =
+        naga::front::wgsl::parse_str(&snippet).map_err(|err| err.emit_to_string(&snippet))?;
+
+    let members = module
+        .types
+        .iter()
+        .find_map(|(_, ty)| match &ty.inner {
+            TypeInner::Struct { members, .. } if ty.name.as_deref() == Some(&struct_name) => {
+                Some(members)
+            }
+            _ => None,
+        })
+        .ok_or_else(|| format!("{} isn't a struct", struct_name))?;
+    Ok(members
+        .iter()
+        .map(|member| LayoutMember {
+            name: member.name.clone().unwrap_or_default(),
+            ty: type_name_of(&module.types[member.ty].inner),
+            offset: member.offset,
+        })
+        .collect())
+}
+
+/// Removes the comments and preprocessor directives
+fn strip_comments(source: &str) -> String {
+    source
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .map(|line| line.split("//").next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Type of the `var<uniform>` declared with `[[group(1), binding(0)]]`
+fn uniform_struct_name(source: &str) -> Option<String> {
+    let code = strip_comments(source);
+    let mut rest = code.as_str();
+    while let Some(index) = rest.find("var<uniform>") {
+        let (before, after) = rest.split_at(index);
+        let declaration = &after["var<uniform>".len()..];
+        let declaration = &declaration[..declaration.find(';')?];
+        rest = &after["var<uniform>".len()..];
+
+        // The attributes right before the variable, if the previous declaration didn't end there
+        let attributes = &before[before.rfind("[[").unwrap_or(0)..];
+        if attributes.contains(';') {
+            continue;
+        }
+        if attribute_value(attributes, "group") == Some(1)
+            && attribute_value(attributes, "binding") == Some(0)
+        {
+            return declaration.split(':').nth(1).map(|ty| ty.trim().to_owned());
+        }
+    }
+    None
+}
+
+/// Parses the number in `name(number)`
+fn attribute_value(attributes: &str, name: &str) -> Option<u32> {
+    let (_, value) = attributes.split_once(&format!("{}(", name))?;
+    value.split_once(')')?.0.trim().parse().ok()
+}
+
+/// The full `struct name { ... };` declaration
+fn struct_declaration(source: &str, name: &str) -> Option<String> {
+    let code = strip_comments(source);
+    let mut offset = 0;
+    while let Some(index) = code[offset..].find("struct ") {
+        let start = offset + index;
+        offset = start + "struct ".len();
+        let rest = &code[offset..];
+        let declared = rest
+            .split(|c: char| c.is_whitespace() || c == '{')
+            .next()
+            .unwrap_or_default();
+        if declared != name {
+            continue;
+        }
+
+        let end = offset + rest.find('}')? + 1;
+        let end = if code[end..].trim_start().starts_with(';') {
+            end + code[end..].find(';')? + 1
+        } else {
+            end
+        };
+        return Some(code[start..end].to_owned());
+    }
+    None
+}
+
+fn type_name_of(inner: &TypeInner) -> String {
+    let scalar = |kind: ScalarKind, width: u8| match (kind, width) {
+        (ScalarKind::Float, 4) => "f32".to_owned(),
+        (ScalarKind::Sint, 4) => "i32".to_owned(),
+        (ScalarKind::Uint, 4) => "u32".to_owned(),
+        (ScalarKind::Bool, _) => "bool".to_owned(),
+        (kind, width) => format!("{:?}{}", kind, width * 8),
+    };
+    match *inner {
+        TypeInner::Scalar { kind, width } => scalar(kind, width),
+        TypeInner::Vector { size, kind, width } => {
+            format!("vec{}<{}>", size as u8, scalar(kind, width))
+        }
+        TypeInner::Matrix {
+            columns,
+            rows,
+            width,
+        } => format!(
+            "mat{}x{}<{}>",
+            columns as u8,
+            rows as u8,
+            scalar(ScalarKind::Float, width)
+        ),
+        ref other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        asset::HandleId, prelude::*, reflect::TypeUuid, render::render_resource::ShaderStages,
+    };
+    use shader_material_derive::ShaderMaterial;
+
+    use super::*;
+    use crate::{custom_material::CustomMaterial, gradient::GradientMaterial};
+
+    /// A vertex only uniform with a color texture and a height texture read by both stages
+    #[derive(Debug, Clone, TypeUuid, ShaderMaterial)]
+    #[uuid = "0f3c1a52-7d9e-4b8a-a1e6-5c2d8f4b9e13"]
+    #[shader("shaders/textured.wgsl")]
+    #[visibility(vertex)]
+    struct TexturedMaterial {
+        scale: f32,
+        #[texture(1)]
+        #[sampler(2)]
+        color_texture: Option<Handle<Image>>,
+        #[texture(3)]
+        #[sampler(4, filter = "nearest")]
+        #[visibility(vertex, fragment)]
+        height_texture: Option<Handle<Image>>,
+    }
+
+    fn assert_matches<M: ShaderMaterial>() {
+        if let Err(diff) = check::<M>() {
+            panic!("{}", diff);
+        }
+    }
+
+    #[test]
+    fn custom_material_matches_shader() {
+        assert_matches::<CustomMaterial>();
+    }
+
+    #[test]
+    fn gradient_material_matches_shader() {
+        assert_matches::<GradientMaterial>();
+    }
+
+    #[test]
+    fn textured_material_matches_shader() {
+        let source = "
+            struct TexturedMaterial {
+                scale: f32;
+            };
+
+            [[group(1), binding(0)]]
+            var<uniform> material: TexturedMaterial;
+            [[group(1), binding(1)]]
+            var color_texture: texture_2d<f32>;
+            [[group(1), binding(2)]]
+            var color_sampler: sampler;
+            [[group(1), binding(3)]]
+            var height_texture: texture_2d<f32>;
+            [[group(1), binding(4)]]
+            var height_sampler: sampler;
+        ";
+        let layout = TexturedMaterial::uniform_layout().unwrap();
+        if let Err(diff) = compare("TexturedMaterial", &layout, source) {
+            panic!("{}", diff);
+        }
+
+        // The textures and samplers are seen by the fragment stage unless set otherwise
+        let visibility: Vec<_> = TexturedMaterial::bind_group_layout_entries()
+            .iter()
+            .map(|entry| (entry.binding, entry.visibility))
+            .collect();
+        assert_eq!(
+            visibility,
+            [
+                (0, ShaderStages::VERTEX),
+                (1, ShaderStages::FRAGMENT),
+                (2, ShaderStages::FRAGMENT),
+                (3, ShaderStages::VERTEX_FRAGMENT),
+                (4, ShaderStages::VERTEX_FRAGMENT),
+            ]
+        );
+
+        let color = Handle::weak(HandleId::random::<Image>());
+        let material = TexturedMaterial {
+            scale: 1.0,
+            color_texture: Some(color.clone()),
+            height_texture: None,
+        };
+        assert_eq!(material.textures(), [Some(color), None]);
+    }
+
+    #[test]
+    fn reordered_members_are_reported() {
+        let layout = MaterialLayout {
+            shader: "test.wgsl",
+            members: vec![
+                LayoutMember {
+                    name: "color".into(),
+                    ty: "vec4<f32>".into(),
+                    offset: 0,
+                },
+                LayoutMember {
+                    name: "scale".into(),
+                    ty: "f32".into(),
+                    offset: 16,
+                },
+            ],
+        };
+        let source = "
+            #import playground::globals
+
+            struct Material {
+                scale: f32;
+                // Moved after scale
+                color: vec4<f32>;
+            };
+
+            [[group(1), binding(0)]]
+            var<uniform> material: Material;
+        ";
+
+        let diff = compare("Material", &layout, source).unwrap_err();
+        assert!(diff.contains("! color: vec4<f32> @ 0"), "{}", diff);
+        assert!(diff.contains("scale: f32 @ 0"), "{}", diff);
+    }
+}
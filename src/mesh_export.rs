@@ -0,0 +1,345 @@
+use std::{fmt::Write as _, fs, path::PathBuf};
+
+use anyhow::{bail, Context};
+use bevy::{
+    asset::FileAssetIo,
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_resource::PrimitiveTopology,
+    },
+};
+
+use crate::Label;
+
+/// Folder the inspector exports meshes to, next to `assets/`
+const EXPORT_FOLDER: &str = "exports";
+
+/// File formats a [`Mesh`] can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshFormat {
+    Obj,
+    /// ASCII PLY
+    Ply,
+}
+
+impl MeshFormat {
+    pub const ALL: [MeshFormat; 2] = [MeshFormat::Obj, MeshFormat::Ply];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            MeshFormat::Obj => "obj",
+            MeshFormat::Ply => "ply",
+        }
+    }
+
+    /// Writes `mesh` in this format, `name` is the object name of OBJ files
+    pub fn encode(self, mesh: &Mesh, name: &str) -> anyhow::Result<String> {
+        match self {
+            MeshFormat::Obj => to_obj(mesh, name),
+            MeshFormat::Ply => to_ply(mesh),
+        }
+    }
+}
+
+/// The attributes of a mesh that are exported
+struct MeshData<'a> {
+    positions: &'a [[f32; 3]],
+    normals: Option<&'a [[f32; 3]]>,
+    uvs: Option<&'a [[f32; 2]]>,
+    /// Indices of a triangle list, generated for meshes without indices
+    indices: Vec<u32>,
+}
+
+impl<'a> MeshData<'a> {
+    fn new(mesh: &'a Mesh) -> anyhow::Result<Self> {
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            bail!("only triangle lists can be exported");
+        }
+        let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions,
+            _ => bail!("the mesh has no Float32x3 positions"),
+        };
+        let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            Some(VertexAttributeValues::Float32x3(normals)) => Some(normals.as_slice()),
+            None => None,
+            Some(_) => bail!("the normals of the mesh aren't Float32x3"),
+        };
+        let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float32x2(uvs)) => Some(uvs.as_slice()),
+            None => None,
+            Some(_) => bail!("the UVs of the mesh aren't Float32x2"),
+        };
+        let indices: Vec<u32> = match mesh.indices() {
+            Some(Indices::U16(indices)) => indices.iter().map(|&i| u32::from(i)).collect(),
+            Some(Indices::U32(indices)) => indices.clone(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        if let Some(&index) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+            bail!("index {} is out of bounds", index);
+        }
+        Ok(Self {
+            positions,
+            normals,
+            uvs,
+            indices,
+        })
+    }
+}
+
+/// Writes a mesh as a Wavefront OBJ object.
+///
+/// V is flipped since it points up in OBJ files, the vertices are shared by the normals and UVs
+/// so each face uses the same index for all three.
+pub fn to_obj(mesh: &Mesh, name: &str) -> anyhow::Result<String> {
+    let data = MeshData::new(mesh)?;
+    let mut obj = String::new();
+    writeln!(obj, "# Exported from bevy_shader_playground")?;
+    writeln!(obj, "o {}", name)?;
+    for [x, y, z] in data.positions {
+        writeln!(obj, "v {} {} {}", x, y, z)?;
+    }
+    for [u, v] in data.uvs.unwrap_or_default() {
+        writeln!(obj, "vt {} {}", u, 1.0 - v)?;
+    }
+    for [x, y, z] in data.normals.unwrap_or_default() {
+        writeln!(obj, "vn {} {} {}", x, y, z)?;
+    }
+    for triangle in data.indices.chunks_exact(3) {
+        write!(obj, "f")?;
+        // OBJ indices start at 1
+        for index in triangle.iter().map(|i| i + 1) {
+            match (data.uvs.is_some(), data.normals.is_some()) {
+                (true, true) => write!(obj, " {}/{}/{}", index, index, index)?,
+                (true, false) => write!(obj, " {}/{}", index, index)?,
+                (false, true) => write!(obj, " {}//{}", index, index)?,
+                (false, false) => write!(obj, " {}", index)?,
+            }
+        }
+        writeln!(obj)?;
+    }
+    Ok(obj)
+}
+
+/// Writes a mesh as an ASCII PLY file, with the UVs as `s` and `t` properties like Blender.
+/// V is flipped to point up, as in OBJ files.
+pub fn to_ply(mesh: &Mesh) -> anyhow::Result<String> {
+    let data = MeshData::new(mesh)?;
+    let mut ply = String::new();
+    writeln!(ply, "ply")?;
+    writeln!(ply, "format ascii 1.0")?;
+    writeln!(ply, "comment Exported from bevy_shader_playground")?;
+    writeln!(ply, "element vertex {}", data.positions.len())?;
+    for property in ["x", "y", "z"] {
+        writeln!(ply, "property float {}", property)?;
+    }
+    if data.normals.is_some() {
+        for property in ["nx", "ny", "nz"] {
+            writeln!(ply, "property float {}", property)?;
+        }
+    }
+    if data.uvs.is_some() {
+        for property in ["s", "t"] {
+            writeln!(ply, "property float {}", property)?;
+        }
+    }
+    writeln!(ply, "element face {}", data.indices.len() / 3)?;
+    writeln!(ply, "property list uchar uint vertex_indices")?;
+    writeln!(ply, "end_header")?;
+
+    for (i, [x, y, z]) in data.positions.iter().enumerate() {
+        write!(ply, "{} {} {}", x, y, z)?;
+        if let Some(normals) = data.normals {
+            let [x, y, z] = normals[i];
+            write!(ply, " {} {} {}", x, y, z)?;
+        }
+        if let Some(uvs) = data.uvs {
+            let [u, v] = uvs[i];
+            write!(ply, " {} {}", u, 1.0 - v)?;
+        }
+        writeln!(ply)?;
+    }
+    for triangle in data.indices.chunks_exact(3) {
+        writeln!(ply, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?;
+    }
+    Ok(ply)
+}
+
+/// Path of an exported file in `exports/`, `name` is made safe to use as a file name
+fn export_path(name: &str, format: MeshFormat) -> PathBuf {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    FileAssetIo::get_root_path()
+        .join(EXPORT_FOLDER)
+        .join(format!("{}.{}", name, format.extension()))
+}
+
+fn export(mesh: &Mesh, name: &str, format: MeshFormat) -> anyhow::Result<PathBuf> {
+    let text = format.encode(mesh, name)?;
+    let path = export_path(name, format);
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(&path, text).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Sent by the inspector to export the mesh of an entity to `exports/<label>.<extension>`.
+/// The meshes of a [`Model`](crate::models::Model) are its children, they are exported to
+/// one file each.
+pub struct ExportMesh {
+    pub entity: Entity,
+    pub format: MeshFormat,
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn export_meshes(
+    mut events: EventReader<ExportMesh>,
+    entities: Query<(&Label, Option<&Handle<Mesh>>, Option<&Children>)>,
+    children_meshes: Query<&Handle<Mesh>>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for &ExportMesh { entity, format } in events.iter() {
+        let (label, mesh, children) = match entities.get(entity) {
+            Ok(entity) => entity,
+            Err(_) => continue,
+        };
+        let entity_meshes: Vec<_> = mesh
+            .into_iter()
+            .chain(
+                children
+                    .into_iter()
+                    .flat_map(|children| children.iter())
+                    .filter_map(|&child| children_meshes.get(child).ok()),
+            )
+            .filter_map(|handle| meshes.get(handle))
+            .collect();
+        if entity_meshes.is_empty() {
+            warn!("{} has no mesh to export", label.0);
+        }
+
+        for (i, mesh) in entity_meshes.iter().enumerate() {
+            let name = if entity_meshes.len() == 1 {
+                label.0.clone()
+            } else {
+                format!("{} {}", label.0, i)
+            };
+            match export(mesh, &name, format) {
+                Ok(path) => info!("Exported {} to {}", name, path.display()),
+                Err(err) => error!("Failed to export {}: {:?}", name, err),
+            }
+        }
+    }
+}
+
+/// Exports the meshes of the entities on [`ExportMesh`] events
+pub struct MeshExportPlugin;
+
+impl Plugin for MeshExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ExportMesh>().add_system(export_meshes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::Path};
+
+    use super::*;
+    use crate::shapes::{Cylinder, Grid};
+
+    /// Compares `text` with `tests/golden/<file>`, `UPDATE_GOLDEN=1` writes it instead
+    fn assert_golden(file: &str, text: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("golden")
+            .join(file);
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, text).unwrap();
+            return;
+        }
+        let golden = fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("failed to read {}: {}", path.display(), err));
+        assert!(
+            golden == text,
+            "{} doesn't match the export:\n{}",
+            path.display(),
+            text
+        );
+    }
+
+    fn quad() -> Mesh {
+        Grid {
+            subdivisions_x: 1,
+            subdivisions_z: 1,
+            ..Grid::default()
+        }
+        .into()
+    }
+
+    #[test]
+    fn quad_matches_golden_obj() {
+        assert_golden("quad.obj", &to_obj(&quad(), "quad").unwrap());
+    }
+
+    #[test]
+    fn quad_matches_golden_ply() {
+        assert_golden("quad.ply", &to_ply(&quad()).unwrap());
+    }
+
+    #[test]
+    fn obj_round_trip() {
+        let mesh = Mesh::from(Cylinder::default());
+        let obj = to_obj(&mesh, "cylinder").unwrap();
+        let options = tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ..Default::default()
+        };
+        let (models, _) =
+            tobj::load_obj_buf(&mut obj.as_bytes(), &options, |_| Ok(Default::default())).unwrap();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].name, "cylinder");
+        let loaded = &models[0].mesh;
+
+        // tobj renumbers the vertices in the order they are used, compare each triangle corner
+        let data = MeshData::new(&mesh).unwrap();
+        assert_eq!(loaded.indices.len(), data.indices.len());
+        let (normals, uvs) = (data.normals.unwrap(), data.uvs.unwrap());
+        for (&i, &j) in loaded.indices.iter().zip(&data.indices) {
+            let (i, j) = (i as usize, j as usize);
+            assert_eq!(loaded.positions[3 * i..3 * i + 3], data.positions[j]);
+            assert_eq!(loaded.normals[3 * i..3 * i + 3], normals[j]);
+            let uv = Vec2::new(loaded.texcoords[2 * i], 1.0 - loaded.texcoords[2 * i + 1]);
+            assert!(
+                uv.distance(Vec2::from(uvs[j])) < 1e-6,
+                "{} != {:?}",
+                uv,
+                uvs[j]
+            );
+        }
+    }
+
+    #[test]
+    fn missing_attributes_are_omitted() {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+        );
+
+        let obj = to_obj(&mesh, "triangle").unwrap();
+        assert!(obj.ends_with("f 1 2 3\n"), "{}", obj);
+        assert!(!obj.contains("vn") && !obj.contains("vt"), "{}", obj);
+
+        let ply = to_ply(&mesh).unwrap();
+        assert!(ply.contains("element face 1\n"), "{}", ply);
+        assert!(!ply.contains("property float nx"), "{}", ply);
+        assert!(
+            ply.ends_with("end_header\n0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n"),
+            "{}",
+            ply
+        );
+    }
+}
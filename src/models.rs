@@ -0,0 +1,372 @@
+use std::{fs, path::Path};
+
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, FileAssetIo, HandleId, LoadContext, LoadedAsset},
+    gltf::{Gltf, GltfMesh},
+    pbr::{NotShadowCaster, NotShadowReceiver, SpecializedMaterial},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+use bevy_egui::egui::{self, Ui};
+
+use crate::tangents;
+
+/// Folder under `assets/` the inspector lists the models of
+const MODELS_FOLDER: &str = "models";
+
+/// Extensions of the model files that can be loaded
+const EXTENSIONS: &[&str] = &["obj", "gltf", "glb"];
+
+/// The meshes of an OBJ file, one per object or group.
+/// They are also labeled assets of the file: `models/file.obj#Mesh0`.
+#[derive(Debug, TypeUuid)]
+#[uuid = "9d2b7c4e-1f6a-4b3d-8e0c-5a7f2e9d4b61"]
+pub struct ObjModel {
+    pub meshes: Vec<Handle<Mesh>>,
+}
+
+#[derive(Default)]
+pub struct ObjLoader;
+
+impl AssetLoader for ObjLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let options = tobj::LoadOptions {
+                single_index: true,
+                triangulate: true,
+                ignore_points: true,
+                ignore_lines: true,
+            };
+            // Materials are assigned in the playground, the .mtl files are ignored
+            let (models, _) =
+                tobj::load_obj_buf(&mut &*bytes, &options, |_| Ok(Default::default()))?;
+
+            let meshes = models
+                .iter()
+                .enumerate()
+                .map(|(i, model)| {
+                    let mut mesh = obj_mesh(&model.mesh);
+                    if let Err(err) = tangents::generate_tangents(&mut mesh) {
+                        warn!(
+                            "Failed to generate the tangents of {} in {}: {}",
+                            model.name,
+                            load_context.path().display(),
+                            err
+                        );
+                    }
+                    load_context.set_labeled_asset(&format!("Mesh{}", i), LoadedAsset::new(mesh))
+                })
+                .collect();
+            load_context.set_default_asset(LoadedAsset::new(ObjModel { meshes }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["obj"]
+    }
+}
+
+/// Converts a triangulated OBJ mesh loaded with `single_index`.
+/// Missing normals are computed from the faces, missing UVs are set to zero.
+fn obj_mesh(obj: &tobj::Mesh) -> Mesh {
+    let positions: Vec<[f32; 3]> = obj
+        .positions
+        .chunks_exact(3)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+    let normals = if obj.normals.is_empty() {
+        smooth_normals(&positions, &obj.indices)
+    } else {
+        obj.normals
+            .chunks_exact(3)
+            .map(|n| [n[0], n[1], n[2]])
+            .collect()
+    };
+    let uvs: Vec<[f32; 2]> = if obj.texcoords.is_empty() {
+        vec![[0.0; 2]; positions.len()]
+    } else {
+        // V points up in OBJ files and down in bevy
+        obj.texcoords
+            .chunks_exact(2)
+            .map(|uv| [uv[0], 1.0 - uv[1]])
+            .collect()
+    };
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(obj.indices.clone())));
+    mesh
+}
+
+/// Vertex normals averaged from the normals of the triangles around them, weighted by area
+fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| triangle[k] as usize);
+        let [pa, pb, pc] = [a, b, c].map(|i| Vec3::from(positions[i]));
+        // The length of the cross product is twice the area of the triangle
+        let normal = (pb - pa).cross(pc - pa);
+        for i in [a, b, c] {
+            normals[i] += normal;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| normal.normalize_or_zero().into())
+        .collect()
+}
+
+/// Spawns the meshes of a model file as children of the entity, with the material of the entity.
+///
+/// The entity needs a [`Handle`] to a material and the transform components,
+/// a `MaterialMeshBundle` with a default mesh handle works.
+/// The node transforms of glTF files are ignored, every mesh is spawned at the model origin.
+#[derive(Component)]
+pub struct Model {
+    /// Asset path of an OBJ or glTF file
+    pub path: String,
+}
+
+impl Model {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+/// The loaded file of a [`Model`]
+#[derive(Component)]
+pub enum ModelHandle {
+    Obj(Handle<ObjModel>),
+    Gltf(Handle<Gltf>),
+}
+
+/// Lowercase extension of a file
+fn extension(path: &Path) -> Option<String> {
+    Some(path.extension()?.to_str()?.to_lowercase())
+}
+
+impl ModelHandle {
+    fn load(path: &str, asset_server: &AssetServer) -> Option<Self> {
+        match extension(Path::new(path))?.as_str() {
+            "obj" => Some(Self::Obj(asset_server.load(path))),
+            "gltf" | "glb" => Some(Self::Gltf(asset_server.load(path))),
+            _ => None,
+        }
+    }
+
+    fn id(&self) -> HandleId {
+        match self {
+            Self::Obj(handle) => handle.id,
+            Self::Gltf(handle) => handle.id,
+        }
+    }
+
+    /// Meshes of every primitive of the model, `None` until the file is loaded
+    fn meshes(
+        &self,
+        obj_models: &Assets<ObjModel>,
+        gltfs: &Assets<Gltf>,
+        gltf_meshes: &Assets<GltfMesh>,
+    ) -> Option<Vec<Handle<Mesh>>> {
+        match self {
+            Self::Obj(handle) => Some(obj_models.get(handle)?.meshes.clone()),
+            Self::Gltf(handle) => {
+                let mut meshes = Vec::new();
+                for mesh in &gltfs.get(handle)?.meshes {
+                    let primitives = &gltf_meshes.get(mesh)?.primitives;
+                    meshes.extend(primitives.iter().map(|primitive| primitive.mesh.clone()));
+                }
+                Some(meshes)
+            }
+        }
+    }
+}
+
+/// Marks the models whose meshes haven't been spawned yet
+#[derive(Component)]
+pub struct PendingModel;
+
+/// Loads the file of the models added or changed, and respawns the models whose file was
+/// hot reloaded in case the number of meshes changed
+#[allow(clippy::needless_pass_by_value)]
+fn load_models(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut obj_events: EventReader<AssetEvent<ObjModel>>,
+    mut gltf_events: EventReader<AssetEvent<Gltf>>,
+    changed: Query<(Entity, &Model), Changed<Model>>,
+    loaded: Query<(Entity, &ModelHandle)>,
+) {
+    for (entity, model) in changed.iter() {
+        let mut entity = commands.entity(entity);
+        entity.despawn_descendants();
+        if let Some(handle) = ModelHandle::load(&model.path, &asset_server) {
+            entity.insert(handle).insert(PendingModel);
+        } else {
+            error!("Unsupported model format: {}", model.path);
+            entity.remove::<ModelHandle>();
+        }
+    }
+
+    let modified_obj = obj_events.iter().filter_map(|event| match event {
+        AssetEvent::Modified { handle } => Some(handle.id),
+        _ => None,
+    });
+    let modified_gltf = gltf_events.iter().filter_map(|event| match event {
+        AssetEvent::Modified { handle } => Some(handle.id),
+        _ => None,
+    });
+    for id in modified_obj.chain(modified_gltf) {
+        for (entity, _) in loaded.iter().filter(|(_, handle)| handle.id() == id) {
+            let mut entity = commands.entity(entity);
+            entity.despawn_descendants();
+            entity.insert(PendingModel);
+        }
+    }
+}
+
+/// Generates the tangents of the glTF meshes that don't have any, like [`ObjLoader`] does for
+/// OBJ files. The meshes are labeled assets of the glTF loader so they are updated once loaded.
+#[allow(clippy::needless_pass_by_value)]
+fn generate_gltf_tangents(
+    mut events: EventReader<AssetEvent<Gltf>>,
+    gltfs: Res<Assets<Gltf>>,
+    gltf_meshes: Res<Assets<GltfMesh>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        let gltf = match gltfs.get(handle) {
+            Some(gltf) => gltf,
+            None => continue,
+        };
+        let primitives = gltf
+            .meshes
+            .iter()
+            .filter_map(|mesh| gltf_meshes.get(mesh))
+            .flat_map(|mesh| &mesh.primitives);
+        for primitive in primitives {
+            let has_tangents = matches!(
+                meshes.get(&primitive.mesh),
+                Some(mesh) if mesh.attribute(Mesh::ATTRIBUTE_TANGENT).is_some()
+            );
+            if has_tangents {
+                continue;
+            }
+            if let Some(mesh) = meshes.get_mut(&primitive.mesh) {
+                if let Err(err) = tangents::generate_tangents(mesh) {
+                    warn!(
+                        "Failed to generate the tangents of {:?}: {}",
+                        primitive.mesh, err
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Spawns the meshes of the loaded models using the material `M`.
+/// The children copy the shadow settings of the model.
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+pub fn spawn_model_meshes<M: SpecializedMaterial>(
+    mut commands: Commands,
+    models: Query<
+        (
+            Entity,
+            &ModelHandle,
+            &Handle<M>,
+            Option<&NotShadowCaster>,
+            Option<&NotShadowReceiver>,
+        ),
+        With<PendingModel>,
+    >,
+    obj_models: Res<Assets<ObjModel>>,
+    gltfs: Res<Assets<Gltf>>,
+    gltf_meshes: Res<Assets<GltfMesh>>,
+) {
+    for (entity, handle, material, not_caster, not_receiver) in models.iter() {
+        let meshes = match handle.meshes(&obj_models, &gltfs, &gltf_meshes) {
+            Some(meshes) => meshes,
+            None => continue,
+        };
+        commands
+            .entity(entity)
+            .remove::<PendingModel>()
+            .with_children(|parent| {
+                for mesh in meshes {
+                    let mut child = parent.spawn_bundle(MaterialMeshBundle {
+                        mesh,
+                        material: material.clone(),
+                        ..Default::default()
+                    });
+                    if not_caster.is_some() {
+                        child.insert(NotShadowCaster);
+                    }
+                    if not_receiver.is_some() {
+                        child.insert(NotShadowReceiver);
+                    }
+                }
+            });
+    }
+}
+
+/// Asset paths of the model files in `assets/models/`, sorted alphabetically
+fn list() -> Vec<String> {
+    let mut paths: Vec<_> = fs::read_dir(
+        FileAssetIo::get_root_path()
+            .join("assets")
+            .join(MODELS_FOLDER),
+    )
+    .into_iter()
+    .flatten()
+    .flatten()
+    .map(|entry| entry.path())
+    .filter(|path| matches!(extension(path), Some(ext) if EXTENSIONS.contains(&ext.as_str())))
+    .filter_map(|path| Some(format!("{}/{}", MODELS_FOLDER, path.file_name()?.to_str()?)))
+    .collect();
+    paths.sort();
+    paths
+}
+
+/// Model dropdown, returns the path of the model selected this frame
+pub fn inspector(ui: &mut Ui, id: Entity, model: &Model) -> Option<String> {
+    let mut selected = None;
+    ui.horizontal(|ui| {
+        ui.label("Model: ");
+        egui::ComboBox::from_id_source(("model", id))
+            .selected_text(model.path.as_str())
+            .show_ui(ui, |ui| {
+                for path in list() {
+                    if ui.selectable_label(path == model.path, &path).clicked() {
+                        selected = Some(path);
+                    }
+                }
+            });
+    });
+    selected
+}
+
+/// Loads the OBJ and glTF files of the [`Model`] components.
+/// `ShaderMaterialPlugin` spawns the meshes of the models using its material.
+pub struct ModelPlugin;
+
+impl Plugin for ModelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ObjModel>()
+            .init_asset_loader::<ObjLoader>()
+            .add_system(load_models)
+            .add_system(generate_gltf_tangents)
+            .add_system(spawn_model_meshes::<StandardMaterial>);
+    }
+}
@@ -0,0 +1,109 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use bevy::{
+    prelude::*,
+    render::camera::{DepthCalculation, ScalingMode},
+};
+use bevy_egui::EguiContext;
+
+use crate::{
+    camera::{Orbit, PanOrbitCamera, PanOrbitCameraControls},
+    fly_camera::FlyCamera,
+};
+
+/// Seconds the camera takes to turn to an aligned view
+const VIEW_DURATION: f32 = 0.3;
+
+/// Present on a [`PanOrbitCamera`] in orthographic projection, keeping the perspective projection
+/// it switches back to
+#[derive(Component)]
+pub struct OrthographicCamera {
+    perspective: PerspectiveProjection,
+}
+
+/// Yaw and pitch of the aligned view whose key was just pressed, if any
+fn aligned_view(controls: &PanOrbitCameraControls, input_keys: &Input<KeyCode>) -> Option<Vec2> {
+    let keys = &controls.keys;
+    let (view, opposite_view) = if input_keys.just_pressed(keys.front_view) {
+        (Vec2::ZERO, Vec2::new(PI, 0.0))
+    } else if input_keys.just_pressed(keys.side_view) {
+        (Vec2::new(FRAC_PI_2, 0.0), Vec2::new(-FRAC_PI_2, 0.0))
+    } else if input_keys.just_pressed(keys.top_view) {
+        (Vec2::new(0.0, -FRAC_PI_2), Vec2::new(0.0, FRAC_PI_2))
+    } else {
+        return None;
+    };
+    if keys.opposite_view.held(input_keys) {
+        Some(opposite_view)
+    } else {
+        Some(view)
+    }
+}
+
+/// Switches the orbit cameras between perspective and orthographic projection with the
+/// `projection` key of their controls, keeping the focus plane the same size. Like in Blender, the
+/// front, side and top view keys turn the camera to look along an axis in orthographic projection.
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+pub fn toggle_orthographic(
+    mut commands: Commands,
+    input_keys: Res<Input<KeyCode>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut PanOrbitCamera,
+            &PanOrbitCameraControls,
+            Option<&PerspectiveProjection>,
+            Option<&OrthographicCamera>,
+        ),
+        Without<FlyCamera>,
+    >,
+    mut egui_context: ResMut<EguiContext>,
+) {
+    if egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    for (entity, mut pan_orbit, controls, perspective, orthographic) in query.iter_mut() {
+        let view = aligned_view(controls, &input_keys);
+        let toggle = if view.is_some() {
+            orthographic.is_none()
+        } else {
+            input_keys.just_pressed(controls.keys.projection)
+        };
+
+        if toggle {
+            if let Some(orthographic) = orthographic {
+                let perspective = orthographic.perspective.clone();
+                pan_orbit.match_orthographic(perspective.fov);
+                commands
+                    .entity(entity)
+                    .remove::<OrthographicProjection>()
+                    .remove::<OrthographicCamera>()
+                    .insert(perspective);
+            } else if let Some(perspective) = perspective {
+                pan_orbit.match_perspective(perspective.fov);
+                let projection = OrthographicProjection {
+                    scale: pan_orbit.current.scale,
+                    scaling_mode: ScalingMode::FixedVertical,
+                    depth_calculation: DepthCalculation::Distance,
+                    ..Default::default()
+                };
+                commands
+                    .entity(entity)
+                    .remove::<PerspectiveProjection>()
+                    .insert(projection)
+                    .insert(OrthographicCamera {
+                        perspective: perspective.clone(),
+                    });
+            }
+        }
+
+        if let Some(view) = view {
+            let orbit = Orbit {
+                yaw: view.x,
+                pitch: view.y,
+                ..pan_orbit.target
+            };
+            pan_orbit.animate_to(orbit, VIEW_DURATION);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::Context;
+use bevy::{asset::FileAssetIo, prelude::*};
+use bevy_egui::egui::{self, Ui};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::scene::TransformDescription;
+
+/// A material whose parameters can be saved to and loaded from `assets/presets/<FOLDER>/<name>.ron`
+pub trait Preset: Clone + Serialize + DeserializeOwned {
+    /// Folder under `assets/presets/` holding the presets of this material
+    const FOLDER: &'static str;
+
+    /// Replaces the parameters of the material with the ones of a loaded preset
+    fn apply(&mut self, preset: Self) {
+        *self = preset;
+    }
+}
+
+/// Content of a preset file
+#[derive(Serialize, Deserialize)]
+struct PresetFile<M> {
+    material: M,
+    transform: TransformDescription,
+}
+
+fn presets_path(folder: &str) -> PathBuf {
+    FileAssetIo::get_root_path()
+        .join("assets")
+        .join("presets")
+        .join(folder)
+}
+
+/// Names of the saved presets of `M`, sorted alphabetically
+fn list<M: Preset>() -> Vec<String> {
+    let mut names: Vec<_> = fs::read_dir(presets_path(M::FOLDER))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "ron"))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+        .collect();
+    names.sort();
+    names
+}
+
+fn save<M: Preset>(name: &str, material: &M, transform: &Transform) -> anyhow::Result<()> {
+    let folder = presets_path(M::FOLDER);
+    fs::create_dir_all(&folder)?;
+    let preset = PresetFile {
+        material: material.clone(),
+        transform: TransformDescription::from(transform),
+    };
+    let text = ron::ser::to_string_pretty(&preset, ron::ser::PrettyConfig::default())?;
+    let path = folder.join(format!("{}.ron", name));
+    fs::write(&path, text).with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn load<M: Preset>(name: &str, material: &mut M, transform: &mut Transform) -> anyhow::Result<()> {
+    let path = presets_path(M::FOLDER).join(format!("{}.ron", name));
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    let preset: PresetFile<M> = ron::from_str(&text)?;
+    material.apply(preset.material);
+    *transform = Transform::from(&preset.transform);
+    Ok(())
+}
+
+/// Preset dropdown, name field and save/load buttons for a single material.
+/// `name` holds the preset name being edited and must be kept between frames.
+/// Returns whether a preset was loaded into `material` and `transform`.
+pub fn inspector<M: Preset>(
+    ui: &mut Ui,
+    id: Entity,
+    name: &mut String,
+    material: &mut M,
+    transform: &mut Transform,
+) -> bool {
+    ui.horizontal(|ui| {
+        ui.label("Preset: ");
+        egui::ComboBox::from_id_source((M::FOLDER, id))
+            .selected_text(name.as_str())
+            .show_ui(ui, |ui| {
+                for preset in list::<M>() {
+                    ui.selectable_value(name, preset.clone(), preset);
+                }
+            });
+    });
+    ui.horizontal(|ui| {
+        let mut loaded = false;
+        ui.text_edit_singleline(name);
+        let valid_name = !name.is_empty() && !name.contains(['/', '\\', '.']);
+        if ui
+            .add_enabled(valid_name, egui::Button::new("Save preset"))
+            .clicked()
+        {
+            if let Err(err) = save(name, material, transform) {
+                error!("Failed to save preset {}: {:?}", name, err);
+            }
+        }
+        if ui
+            .add_enabled(valid_name, egui::Button::new("Load preset"))
+            .clicked()
+        {
+            match load(name, material, transform) {
+                Ok(()) => loaded = true,
+                Err(err) => error!("Failed to load preset {}: {:?}", name, err),
+            }
+        }
+        loaded
+    })
+    .inner
+}
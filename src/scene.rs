@@ -0,0 +1,327 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    ecs::system::SystemParam,
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    custom_material::CustomMaterial,
+    dynamic_material::{DynamicMaterial, UniformValue},
+    gradient::GradientMaterial,
+    models::Model,
+    sdf, shapes, tangents, Label,
+};
+
+/// Scene loaded on startup, edits to it are hot reloaded
+const DEFAULT_SCENE: &str = "scenes/default.scene.ron";
+
+/// Everything spawned by the playground apart from the camera, loaded from a `.scene.ron` file
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "3c1f1b0e-6a3f-4d7e-9a55-0b8f3e2d7c41"]
+pub struct SceneDescription {
+    #[serde(default)]
+    pub lights: Vec<LightDescription>,
+    #[serde(default)]
+    pub entities: Vec<EntityDescription>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LightDescription {
+    pub translation: Vec3,
+    pub intensity: f32,
+    #[serde(default)]
+    pub shadows: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EntityDescription {
+    /// Name shown in the inspector
+    #[serde(default)]
+    pub label: Option<String>,
+    pub mesh: MeshDescription,
+    #[serde(default)]
+    pub transform: TransformDescription,
+    pub material: MaterialDescription,
+    /// Whether the entity casts and receives shadows
+    #[serde(default)]
+    pub shadows: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub enum MeshDescription {
+    Cube {
+        size: f32,
+    },
+    Plane {
+        size: f32,
+    },
+    UVSphere {
+        radius: f32,
+        sectors: usize,
+        stacks: usize,
+    },
+    Cylinder(shapes::Cylinder),
+    Torus(shapes::Torus),
+    Cone(shapes::Cone),
+    Capsule(shapes::Capsule),
+    Icosphere(shapes::Icosphere),
+    Grid(shapes::Grid),
+    /// Asset path of an OBJ or glTF file, usually in `models/`
+    Model(String),
+    /// Meshed from a signed distance function
+    Sdf(sdf::SdfMesh),
+}
+
+impl MeshDescription {
+    /// The generated mesh, `None` for a [`MeshDescription::Model`] whose meshes are loaded from
+    /// its file
+    fn to_mesh(&self) -> Option<Mesh> {
+        Some(match *self {
+            MeshDescription::Cube { size } => Mesh::from(shape::Cube { size }),
+            MeshDescription::Plane { size } => Mesh::from(shape::Plane { size }),
+            MeshDescription::UVSphere {
+                radius,
+                sectors,
+                stacks,
+            } => Mesh::from(shape::UVSphere {
+                radius,
+                sectors,
+                stacks,
+            }),
+            MeshDescription::Cylinder(ref cylinder) => Mesh::from(cylinder.clone()),
+            MeshDescription::Torus(ref torus) => Mesh::from(torus.clone()),
+            MeshDescription::Cone(ref cone) => Mesh::from(cone.clone()),
+            MeshDescription::Capsule(ref capsule) => Mesh::from(capsule.clone()),
+            MeshDescription::Icosphere(ref icosphere) => Mesh::from(icosphere.clone()),
+            MeshDescription::Grid(ref grid) => Mesh::from(grid.clone()),
+            MeshDescription::Sdf(ref sdf) => Mesh::from(sdf.clone()),
+            MeshDescription::Model(_) => return None,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TransformDescription {
+    pub translation: Vec3,
+    /// Euler angles in degrees, applied in XYZ order
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+impl Default for TransformDescription {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Vec3::ZERO,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl From<&TransformDescription> for Transform {
+    fn from(description: &TransformDescription) -> Self {
+        Transform {
+            translation: description.translation,
+            rotation: Quat::from_euler(
+                EulerRot::XYZ,
+                description.rotation.x.to_radians(),
+                description.rotation.y.to_radians(),
+                description.rotation.z.to_radians(),
+            ),
+            scale: description.scale,
+        }
+    }
+}
+
+impl From<&Transform> for TransformDescription {
+    fn from(transform: &Transform) -> Self {
+        let (x, y, z) = transform.rotation.to_euler(EulerRot::XYZ);
+        Self {
+            translation: transform.translation,
+            rotation: Vec3::new(x.to_degrees(), y.to_degrees(), z.to_degrees()),
+            scale: transform.scale,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub enum MaterialDescription {
+    Standard {
+        color: Color,
+    },
+    Custom(CustomMaterial),
+    Gradient(GradientMaterial),
+    /// A [`DynamicMaterial`], `values` sets the initial value of some of its uniform members
+    Dynamic {
+        shader: String,
+        #[serde(default)]
+        values: HashMap<String, UniformValue>,
+    },
+}
+
+#[derive(Default)]
+pub struct SceneDescriptionLoader;
+
+impl AssetLoader for SceneDescriptionLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let scene = ron::de::from_bytes::<SceneDescription>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(scene));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["scene.ron"]
+    }
+}
+
+/// The scene description currently spawned in the world
+pub struct CurrentScene(pub Handle<SceneDescription>);
+
+/// Marks entities spawned from the [`CurrentScene`] so they can be replaced when it's reloaded
+#[derive(Component)]
+pub struct SceneEntity;
+
+pub struct SceneDescriptionPlugin;
+
+impl Plugin for SceneDescriptionPlugin {
+    fn build(&self, app: &mut App) {
+        let scene = app.world.resource::<AssetServer>().load(DEFAULT_SCENE);
+        app.add_asset::<SceneDescription>()
+            .init_asset_loader::<SceneDescriptionLoader>()
+            .insert_resource(CurrentScene(scene))
+            .add_system(spawn_scene);
+    }
+}
+
+#[derive(SystemParam)]
+struct SceneAssets<'w, 's> {
+    meshes: ResMut<'w, Assets<Mesh>>,
+    standard_materials: ResMut<'w, Assets<StandardMaterial>>,
+    custom_materials: ResMut<'w, Assets<CustomMaterial>>,
+    gradient_materials: ResMut<'w, Assets<GradientMaterial>>,
+    dynamic_materials: ResMut<'w, Assets<DynamicMaterial>>,
+    asset_server: Res<'w, AssetServer>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+/// Spawns the [`CurrentScene`] once it's loaded and respawns it every time the file changes
+#[allow(clippy::needless_pass_by_value)]
+fn spawn_scene(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<SceneDescription>>,
+    current_scene: Res<CurrentScene>,
+    scenes: Res<Assets<SceneDescription>>,
+    spawned: Query<Entity, With<SceneEntity>>,
+    mut assets: SceneAssets,
+) {
+    let reloaded = events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+            *handle == current_scene.0
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+    if !reloaded {
+        return;
+    }
+    let scene = match scenes.get(&current_scene.0) {
+        Some(scene) => scene,
+        None => return,
+    };
+
+    for entity in spawned.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for light in &scene.lights {
+        commands
+            .spawn_bundle(PointLightBundle {
+                point_light: PointLight {
+                    intensity: light.intensity,
+                    shadows_enabled: light.shadows,
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(light.translation),
+                ..Default::default()
+            })
+            .insert(SceneEntity);
+    }
+
+    for description in &scene.entities {
+        let transform = Transform::from(&description.transform);
+        let mut entity = commands.spawn();
+        entity.insert(SceneEntity);
+
+        // The meshes of a model are spawned as children of the entity once its file is loaded
+        let mesh = match description.mesh.to_mesh() {
+            Some(mut mesh) => {
+                if let Err(err) = tangents::generate_tangents(&mut mesh) {
+                    warn!(
+                        "Failed to generate the tangents of {:?}: {}",
+                        description.mesh, err
+                    );
+                }
+                assets.meshes.add(mesh)
+            }
+            None => Handle::default(),
+        };
+        if let MeshDescription::Model(path) = &description.mesh {
+            entity.insert(Model::new(path.as_str()));
+        }
+        match &description.material {
+            MaterialDescription::Standard { color } => {
+                entity.insert_bundle(PbrBundle {
+                    mesh,
+                    transform,
+                    material: assets.standard_materials.add((*color).into()),
+                    ..Default::default()
+                });
+            }
+            MaterialDescription::Custom(material) => {
+                entity.insert_bundle(MaterialMeshBundle {
+                    mesh,
+                    transform,
+                    material: assets.custom_materials.add(material.clone()),
+                    ..Default::default()
+                });
+            }
+            MaterialDescription::Gradient(material) => {
+                entity.insert_bundle(MaterialMeshBundle {
+                    mesh,
+                    transform,
+                    material: assets.gradient_materials.add(material.clone()),
+                    ..Default::default()
+                });
+            }
+            MaterialDescription::Dynamic { shader, values } => {
+                let material = DynamicMaterial::new(assets.asset_server.load(shader.as_str()))
+                    .with_values(values.clone());
+                entity.insert_bundle(MaterialMeshBundle {
+                    mesh,
+                    transform,
+                    material: assets.dynamic_materials.add(material),
+                    ..Default::default()
+                });
+            }
+        }
+        if let Some(label) = &description.label {
+            entity.insert(Label(label.clone()));
+        }
+        if !description.shadows {
+            entity.insert_bundle((NotShadowCaster, NotShadowReceiver));
+        }
+    }
+}
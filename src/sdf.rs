@@ -0,0 +1,542 @@
+use std::fmt::Write as _;
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+use serde::Deserialize;
+
+/// A signed distance function built from primitives and boolean operations.
+///
+/// It can be evaluated on the CPU, meshed with [`surface_nets`], or written as WGSL with
+/// [`Sdf::to_wgsl`] using the functions of `shaders/sdf.wgsl`, so the same shape can be
+/// raymarched in a shader.
+#[derive(Debug, Clone, Deserialize)]
+pub enum Sdf {
+    Sphere {
+        radius: f32,
+    },
+    /// A box centered on the origin
+    Cuboid {
+        half_size: Vec3,
+    },
+    /// A torus lying on the XZ plane, around the Y axis
+    Torus {
+        radius: f32,
+        ring_radius: f32,
+    },
+    /// A cylinder along the Y axis, centered on the origin
+    Cylinder {
+        radius: f32,
+        height: f32,
+    },
+    Translate(Vec3, Box<Sdf>),
+    Union(Box<Sdf>, Box<Sdf>),
+    Intersection(Box<Sdf>, Box<Sdf>),
+    /// The first shape minus the second one
+    Difference(Box<Sdf>, Box<Sdf>),
+    /// Union blending the surfaces closer than the third parameter
+    SmoothUnion(Box<Sdf>, Box<Sdf>, f32),
+}
+
+impl Sdf {
+    /// Signed distance from `p` to the surface, negative inside.
+    /// Same as the WGSL functions in `shaders/sdf.wgsl`.
+    pub fn distance(&self, p: Vec3) -> f32 {
+        match self {
+            Sdf::Sphere { radius } => p.length() - radius,
+            Sdf::Cuboid { half_size } => {
+                let q = p.abs() - *half_size;
+                q.max(Vec3::ZERO).length() + q.max_element().min(0.0)
+            }
+            Sdf::Torus {
+                radius,
+                ring_radius,
+            } => Vec2::new(Vec2::new(p.x, p.z).length() - radius, p.y).length() - ring_radius,
+            Sdf::Cylinder { radius, height } => {
+                let d = Vec2::new(Vec2::new(p.x, p.z).length(), p.y).abs()
+                    - Vec2::new(*radius, height * 0.5);
+                d.max_element().min(0.0) + d.max(Vec2::ZERO).length()
+            }
+            Sdf::Translate(offset, sdf) => sdf.distance(p - *offset),
+            Sdf::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Sdf::Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            Sdf::Difference(a, b) => a.distance(p).max(-b.distance(p)),
+            Sdf::SmoothUnion(a, b, k) => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                let blend = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+                b + (a - b) * blend - k * blend * (1.0 - blend)
+            }
+        }
+    }
+
+    /// A WGSL function `fn <name>(p: vec3<f32>) -> f32` computing the same distance,
+    /// it needs `#import playground::sdf`
+    pub fn to_wgsl(&self, name: &str) -> String {
+        format!(
+            "fn {}(p: vec3<f32>) -> f32 {{\n    return {};\n}}\n",
+            name,
+            self.wgsl_expression("p")
+        )
+    }
+
+    /// The distance at the WGSL expression `p`
+    fn wgsl_expression(&self, p: &str) -> String {
+        let mut wgsl = String::new();
+        match self {
+            Sdf::Sphere { radius } => write!(wgsl, "sdf_sphere({}, {})", p, float(*radius)),
+            Sdf::Cuboid { half_size } => {
+                write!(wgsl, "sdf_cuboid({}, {})", p, vec3(*half_size))
+            }
+            Sdf::Torus {
+                radius,
+                ring_radius,
+            } => write!(
+                wgsl,
+                "sdf_torus({}, {}, {})",
+                p,
+                float(*radius),
+                float(*ring_radius)
+            ),
+            Sdf::Cylinder { radius, height } => write!(
+                wgsl,
+                "sdf_cylinder({}, {}, {})",
+                p,
+                float(*radius),
+                float(*height)
+            ),
+            Sdf::Translate(offset, sdf) => {
+                let p = format!("({} - {})", p, vec3(*offset));
+                write!(wgsl, "{}", sdf.wgsl_expression(&p))
+            }
+            Sdf::Union(a, b) => write!(
+                wgsl,
+                "min({}, {})",
+                a.wgsl_expression(p),
+                b.wgsl_expression(p)
+            ),
+            Sdf::Intersection(a, b) => write!(
+                wgsl,
+                "max({}, {})",
+                a.wgsl_expression(p),
+                b.wgsl_expression(p)
+            ),
+            Sdf::Difference(a, b) => write!(
+                wgsl,
+                "max({}, -{})",
+                a.wgsl_expression(p),
+                b.wgsl_expression(p)
+            ),
+            Sdf::SmoothUnion(a, b, k) => write!(
+                wgsl,
+                "sdf_smooth_union({}, {}, {})",
+                a.wgsl_expression(p),
+                b.wgsl_expression(p),
+                float(*k)
+            ),
+        }
+        .unwrap();
+        wgsl
+    }
+}
+
+/// WGSL float literal, `{:?}` always has a decimal point unlike `{}`
+fn float(value: f32) -> String {
+    format!("{:?}", value)
+}
+
+fn vec3(value: Vec3) -> String {
+    format!(
+        "vec3<f32>({}, {}, {})",
+        float(value.x),
+        float(value.y),
+        float(value.z)
+    )
+}
+
+/// Largest resolution of an [`SdfMesh`], the SDF is sampled `(resolution + 1)³` times
+const MAX_RESOLUTION: u32 = 256;
+
+/// Smallest half size of the box of an [`SdfMesh`]
+const MIN_HALF_SIZE: f32 = 0.01;
+
+/// An [`Sdf`] meshed with [`surface_nets`] inside a box centered on the origin
+#[derive(Debug, Clone, Deserialize)]
+pub struct SdfMesh {
+    pub sdf: Sdf,
+    /// Half size of the box sampled, the shape needs to be inside of it to be closed
+    #[serde(default = "SdfMesh::default_half_size")]
+    pub half_size: Vec3,
+    /// Number of cells along each axis of the box
+    #[serde(default = "SdfMesh::default_resolution")]
+    pub resolution: u32,
+}
+
+impl SdfMesh {
+    fn default_half_size() -> Vec3 {
+        Vec3::ONE
+    }
+
+    fn default_resolution() -> u32 {
+        48
+    }
+}
+
+impl From<SdfMesh> for Mesh {
+    fn from(mesh: SdfMesh) -> Self {
+        // The settings come from a scene file, they are fixed rather than panicking on reload
+        let resolution = mesh.resolution.clamp(2, MAX_RESOLUTION);
+        let half_size = mesh.half_size.abs().max(Vec3::splat(MIN_HALF_SIZE));
+        if resolution != mesh.resolution || half_size != mesh.half_size {
+            warn!(
+                "Invalid SDF mesh resolution {} or half size {}, using {} and {}",
+                mesh.resolution, mesh.half_size, resolution, half_size
+            );
+        }
+        surface_nets(|p| mesh.sdf.distance(p), -half_size, half_size, resolution)
+    }
+}
+
+/// Meshes the surface where `sdf` is zero between `min` and `max`, with naive surface nets.
+///
+/// This is the simplest dual contouring: the box is split in `resolution` cells along each
+/// axis, each cell the surface goes through gets a vertex at the average of the points where
+/// the surface crosses its edges, and every edge crossing the surface becomes a quad joining
+/// the vertices of the four cells around it. The normals are the gradient of `sdf`,
+/// the UVs are the position projected on the XZ plane of the box.
+///
+/// Panics if `resolution` is less than 2 or `max` isn't greater than `min` on every axis.
+pub fn surface_nets(sdf: impl Fn(Vec3) -> f32, min: Vec3, max: Vec3, resolution: u32) -> Mesh {
+    assert!(resolution > 1 && max.cmpgt(min).all());
+    let n = resolution as usize;
+    let cell_size = (max - min) / resolution as f32;
+    let point = |[i, j, k]: [usize; 3]| min + Vec3::new(i as f32, j as f32, k as f32) * cell_size;
+
+    // Samples of the SDF at the corners of the cells
+    let sample_index = |[i, j, k]: [usize; 3]| i + (n + 1) * (j + (n + 1) * k);
+    let mut samples = vec![0.0; (n + 1).pow(3)];
+    for k in 0..=n {
+        for j in 0..=n {
+            for i in 0..=n {
+                samples[sample_index([i, j, k])] = sdf(point([i, j, k]));
+            }
+        }
+    }
+    let inside = |corner: [usize; 3]| samples[sample_index(corner)] < 0.0;
+
+    // One vertex in every cell the surface goes through
+    let epsilon = cell_size.min_element() * 0.01;
+    let gradient = |p: Vec3| {
+        Vec3::new(
+            sdf(p + Vec3::X * epsilon) - sdf(p - Vec3::X * epsilon),
+            sdf(p + Vec3::Y * epsilon) - sdf(p - Vec3::Y * epsilon),
+            sdf(p + Vec3::Z * epsilon) - sdf(p - Vec3::Z * epsilon),
+        )
+    };
+    let cell_index = |[i, j, k]: [usize; 3]| i + n * (j + n * k);
+    let mut cell_vertices = vec![None; n.pow(3)];
+    let (mut positions, mut normals, mut uvs) = (Vec::new(), Vec::new(), Vec::new());
+    for k in 0..n {
+        for j in 0..n {
+            for i in 0..n {
+                let mut sum = Vec3::ZERO;
+                let mut crossings = 0;
+                for (a, b) in CELL_EDGES {
+                    let a = [i + a[0], j + a[1], k + a[2]];
+                    let b = [i + b[0], j + b[1], k + b[2]];
+                    if inside(a) == inside(b) {
+                        continue;
+                    }
+                    let (distance_a, distance_b) =
+                        (samples[sample_index(a)], samples[sample_index(b)]);
+                    let t = distance_a / (distance_a - distance_b);
+                    sum += point(a).lerp(point(b), t);
+                    crossings += 1;
+                }
+                if crossings == 0 {
+                    continue;
+                }
+
+                let position = sum / crossings as f32;
+                let uv = (Vec2::new(position.x, position.z) - Vec2::new(min.x, min.z))
+                    / Vec2::new(max.x - min.x, max.z - min.z);
+                cell_vertices[cell_index([i, j, k])] = Some(positions.len() as u32);
+                positions.push(position.to_array());
+                normals.push(gradient(position).normalize_or_zero().to_array());
+                uvs.push(uv.to_array());
+            }
+        }
+    }
+
+    // A quad for every edge crossing the surface, facing the outside
+    let mut indices = Vec::new();
+    for k in 0..=n {
+        for j in 0..=n {
+            for i in 0..=n {
+                let corner = [i, j, k];
+                for axis in 0..3 {
+                    // The two other axes, in the order making a right-handed basis with `axis`
+                    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                    if corner[axis] == n
+                        || corner[u] == 0
+                        || corner[u] == n
+                        || corner[v] == 0
+                        || corner[v] == n
+                    {
+                        continue;
+                    }
+                    let mut next = corner;
+                    next[axis] += 1;
+                    if inside(corner) == inside(next) {
+                        continue;
+                    }
+
+                    // The cells around the edge, counterclockwise seen from the end of `axis`
+                    let cell = |du: usize, dv: usize| {
+                        let mut cell = corner;
+                        cell[u] = cell[u] + du - 1;
+                        cell[v] = cell[v] + dv - 1;
+                        cell_vertices[cell_index(cell)].unwrap()
+                    };
+                    let quad = [cell(0, 0), cell(1, 0), cell(1, 1), cell(0, 1)];
+                    // The outside is toward the end of `axis` when the start is inside
+                    if inside(corner) {
+                        indices.extend([quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]);
+                    } else {
+                        indices.extend([quad[0], quad[2], quad[1], quad[0], quad[3], quad[2]]);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh
+}
+
+/// The 12 edges of a cell, as offsets of their corners
+const CELL_EDGES: [([usize; 3], [usize; 3]); 12] = [
+    ([0, 0, 0], [1, 0, 0]),
+    ([0, 1, 0], [1, 1, 0]),
+    ([0, 0, 1], [1, 0, 1]),
+    ([0, 1, 1], [1, 1, 1]),
+    ([0, 0, 0], [0, 1, 0]),
+    ([1, 0, 0], [1, 1, 0]),
+    ([0, 0, 1], [0, 1, 1]),
+    ([1, 0, 1], [1, 1, 1]),
+    ([0, 0, 0], [0, 0, 1]),
+    ([1, 0, 0], [1, 0, 1]),
+    ([0, 1, 0], [0, 1, 1]),
+    ([1, 1, 0], [1, 1, 1]),
+];
+
+/// Keeps `shaders/sdf.wgsl` loaded so the material shaders can `#import playground::sdf`
+struct SdfShader(#[allow(dead_code)] Handle<Shader>);
+
+impl FromWorld for SdfShader {
+    fn from_world(world: &mut World) -> Self {
+        Self(world.resource::<AssetServer>().load("shaders/sdf.wgsl"))
+    }
+}
+
+pub struct SdfPlugin;
+
+impl Plugin for SdfPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SdfShader>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use bevy::{
+        asset::FileAssetIo,
+        render::mesh::{MeshVertexAttribute, VertexAttributeValues},
+        utils::HashSet,
+    };
+
+    use super::*;
+    use crate::scene::{MeshDescription, SceneDescription};
+
+    fn read_asset(path: &str) -> String {
+        fs::read_to_string(FileAssetIo::get_root_path().join("assets").join(path)).unwrap()
+    }
+
+    fn float3(mesh: &Mesh, attribute: MeshVertexAttribute) -> Vec<Vec3> {
+        let name = attribute.name;
+        match mesh.attribute(attribute) {
+            Some(VertexAttributeValues::Float32x3(values)) => {
+                values.iter().copied().map(Vec3::from).collect()
+            }
+            _ => panic!("missing {}", name),
+        }
+    }
+
+    fn sphere(radius: f32) -> Sdf {
+        Sdf::Sphere { radius }
+    }
+
+    /// Uses every primitive and operation
+    fn sample() -> Sdf {
+        Sdf::SmoothUnion(
+            Box::new(Sdf::Difference(
+                Box::new(Sdf::Cuboid {
+                    half_size: Vec3::new(0.5, 0.3, 0.4),
+                }),
+                Box::new(Sdf::Translate(
+                    Vec3::new(0.2, 0.0, -0.1),
+                    Box::new(sphere(0.3)),
+                )),
+            )),
+            Box::new(Sdf::Union(
+                Box::new(Sdf::Torus {
+                    radius: 0.6,
+                    ring_radius: 0.1,
+                }),
+                Box::new(Sdf::Intersection(
+                    Box::new(Sdf::Cylinder {
+                        radius: 0.2,
+                        height: 1.5,
+                    }),
+                    Box::new(sphere(0.7)),
+                )),
+            )),
+            0.1,
+        )
+    }
+
+    #[test]
+    fn primitive_distances() {
+        let epsilon = 1e-5;
+        let cuboid = Sdf::Cuboid {
+            half_size: Vec3::new(1.0, 2.0, 3.0),
+        };
+        assert!((cuboid.distance(Vec3::new(3.0, 0.0, 0.0)) - 2.0).abs() < epsilon);
+        assert!((cuboid.distance(Vec3::new(2.0, 3.0, 3.0)) - 2.0_f32.sqrt()).abs() < epsilon);
+        assert!((cuboid.distance(Vec3::ZERO) + 1.0).abs() < epsilon);
+
+        let torus = Sdf::Torus {
+            radius: 1.0,
+            ring_radius: 0.25,
+        };
+        assert!((torus.distance(Vec3::new(0.0, 0.0, 1.0)) + 0.25).abs() < epsilon);
+        assert!(
+            (torus.distance(Vec3::new(0.0, 1.0, 0.0)) - (2.0_f32.sqrt() - 0.25)).abs() < epsilon
+        );
+
+        let cylinder = Sdf::Cylinder {
+            radius: 1.0,
+            height: 2.0,
+        };
+        assert!((cylinder.distance(Vec3::new(0.0, 3.0, 0.0)) - 2.0).abs() < epsilon);
+        assert!((cylinder.distance(Vec3::new(0.0, 0.0, 1.5)) - 0.5).abs() < epsilon);
+        assert!((cylinder.distance(Vec3::new(0.0, 0.5, 0.0)) + 0.5).abs() < epsilon);
+    }
+
+    #[test]
+    fn boolean_operations() {
+        let epsilon = 1e-5;
+        let a = || Box::new(Sdf::Translate(Vec3::X * -0.5, Box::new(sphere(1.0))));
+        let b = || Box::new(Sdf::Translate(Vec3::X * 0.5, Box::new(sphere(1.0))));
+        let distance = |sdf: Sdf, x: f32| sdf.distance(Vec3::X * x);
+
+        assert!(distance(Sdf::Union(a(), b()), 1.5).abs() < epsilon);
+        assert!(distance(Sdf::Union(a(), b()), -1.5).abs() < epsilon);
+        assert!(distance(Sdf::Intersection(a(), b()), 0.5).abs() < epsilon);
+        assert!(distance(Sdf::Intersection(a(), b()), 1.0) > 0.0);
+        assert!(distance(Sdf::Difference(a(), b()), -0.5).abs() < epsilon);
+        assert!(distance(Sdf::Difference(a(), b()), 0.0) > 0.0);
+        // Far from the blend the smooth union is the union, close to it it's thicker
+        assert!(distance(Sdf::SmoothUnion(a(), b(), 0.2), 1.5).abs() < epsilon);
+        let smooth = Sdf::SmoothUnion(a(), b(), 0.2).distance(Vec3::Y * 0.9);
+        assert!(smooth < Sdf::Union(a(), b()).distance(Vec3::Y * 0.9));
+    }
+
+    #[test]
+    fn invalid_mesh_settings_are_fixed() {
+        for (resolution, half_size) in [(0, Vec3::ONE), (1, Vec3::ONE), (8, -Vec3::ONE)] {
+            let mesh = Mesh::from(SdfMesh {
+                sdf: sphere(0.5),
+                half_size,
+                resolution,
+            });
+            assert!(!float3(&mesh, Mesh::ATTRIBUTE_POSITION).is_empty());
+        }
+    }
+
+    #[test]
+    fn sphere_mesh() {
+        let radius = 0.8;
+        let mesh = surface_nets(|p| p.length() - radius, Vec3::splat(-1.0), Vec3::ONE, 24);
+        let positions = float3(&mesh, Mesh::ATTRIBUTE_POSITION);
+        let normals = float3(&mesh, Mesh::ATTRIBUTE_NORMAL);
+        assert!(!positions.is_empty());
+        for (position, normal) in positions.iter().zip(&normals) {
+            assert!((position.length() - radius).abs() < 0.02, "{}", position);
+            assert!(normal.dot(position.normalize()) > 0.999, "{}", normal);
+        }
+
+        let indices: Vec<u32> = match mesh.indices() {
+            Some(Indices::U32(indices)) => indices.clone(),
+            _ => panic!("missing indices"),
+        };
+        let mut edges = HashSet::default();
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| positions[triangle[k] as usize]);
+            // Facing outward
+            assert!((b - a).cross(c - a).dot(a + b + c) > 0.0);
+            for k in 0..3 {
+                assert!(edges.insert((triangle[k], triangle[(k + 1) % 3])));
+            }
+        }
+        // Closed, every edge is shared by two triangles in opposite directions
+        for &(a, b) in &edges {
+            assert!(edges.contains(&(b, a)));
+        }
+    }
+
+    #[test]
+    fn generated_wgsl_is_valid() {
+        let sdf = sample();
+        let library = read_asset("shaders/sdf.wgsl");
+        let library = library.replace("#define_import_path playground::sdf", "");
+        let source = format!("{}\n{}", library, sdf.to_wgsl("sample"));
+        let module = naga::front::wgsl::parse_str(&source)
+            .map_err(|err| err.emit_to_string(&source))
+            .unwrap();
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap();
+    }
+
+    #[test]
+    fn raymarch_shader_matches_scene() {
+        let scene: SceneDescription =
+            ron::from_str(&read_asset("scenes/default.scene.ron")).unwrap();
+        let sdf = scene
+            .entities
+            .iter()
+            .find_map(|entity| match &entity.mesh {
+                MeshDescription::Sdf(mesh) if entity.label.as_deref() == Some("SDF mesh") => {
+                    Some(&mesh.sdf)
+                }
+                _ => None,
+            })
+            .unwrap();
+        let shader = read_asset("shaders/sdf_raymarch.wgsl");
+        assert!(
+            shader.contains(&sdf.to_wgsl("scene_sdf")),
+            "The raymarched SDF doesn't match the scene, expected:\n{}",
+            sdf.to_wgsl("scene_sdf")
+        );
+    }
+}
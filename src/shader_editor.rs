@@ -0,0 +1,356 @@
+use std::fs;
+
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, text::LayoutJob, Color32, RichText, TextFormat, Ui},
+    EguiContext,
+};
+
+use crate::shader_errors::{check_shader, shader_file, shader_path, ShaderError};
+
+/// Time without edits before the text is compiled and applied, in seconds
+const APPLY_DELAY: f64 = 0.5;
+
+const KEYWORDS: &[&str] = &[
+    "fn",
+    "let",
+    "var",
+    "struct",
+    "return",
+    "if",
+    "else",
+    "loop",
+    "for",
+    "break",
+    "continue",
+    "continuing",
+    "switch",
+    "case",
+    "default",
+    "fallthrough",
+    "discard",
+    "type",
+    "true",
+    "false",
+    "private",
+    "function",
+    "uniform",
+    "storage",
+    "workgroup",
+    "read",
+    "write",
+    "read_write",
+];
+
+const TYPES: &[&str] = &[
+    "f32",
+    "i32",
+    "u32",
+    "bool",
+    "array",
+    "ptr",
+    "atomic",
+    "sampler",
+    "sampler_comparison",
+];
+
+const KEYWORD_COLOR: Color32 = Color32::from_rgb(86, 156, 214);
+const TYPE_COLOR: Color32 = Color32::from_rgb(78, 201, 176);
+const NUMBER_COLOR: Color32 = Color32::from_rgb(181, 206, 168);
+const COMMENT_COLOR: Color32 = Color32::from_rgb(106, 153, 85);
+const ATTRIBUTE_COLOR: Color32 = Color32::from_rgb(197, 134, 192);
+const TEXT_COLOR: Color32 = Color32::LIGHT_GRAY;
+const ERROR_BACKGROUND: Color32 = Color32::from_rgba_premultiplied(90, 20, 20, 160);
+
+/// A shader being edited
+struct OpenShader {
+    /// Asset path of the shader
+    path: String,
+    handle: Handle<Shader>,
+    text: String,
+    /// Time of the last edit that hasn't been applied yet
+    edited_at: Option<f64>,
+    /// Error of the current text, the asset keeps its last good version meanwhile
+    error: Option<ShaderError>,
+    /// Whether the text differs from the file on disk
+    unsaved: bool,
+}
+
+impl OpenShader {
+    fn open(path: String, asset_server: &AssetServer) -> Self {
+        let text = fs::read_to_string(shader_file(&path)).unwrap_or_else(|err| {
+            error!("Failed to read shader {}: {}", path, err);
+            String::new()
+        });
+        Self {
+            handle: asset_server.load(path.as_str()),
+            path,
+            text,
+            edited_at: None,
+            error: None,
+            unsaved: false,
+        }
+    }
+
+    /// Compiles the text and replaces the shader asset with it if it's valid
+    fn apply(&mut self, shaders: &mut Assets<Shader>, now: f64) {
+        // The asset path import of the loaded shader is lost, only `#define_import_path` is kept
+        let shader = Shader::from_wgsl(self.text.clone());
+        match check_shader(&self.path, &shader, shaders, &self.text) {
+            Some(Ok(())) => {
+                self.error = None;
+                if let Some(asset) = shaders.get_mut(&self.handle) {
+                    *asset = shader;
+                }
+            }
+            Some(Err(error)) => self.error = Some(error),
+            // An import isn't loaded yet, try again later
+            None => self.edited_at = Some(now),
+        }
+    }
+
+    fn save(&mut self) {
+        let path = shader_file(&self.path);
+        match fs::write(&path, &self.text) {
+            Ok(()) => self.unsaved = false,
+            Err(err) => error!("Failed to write {}: {}", path.display(), err),
+        }
+    }
+}
+
+/// Window editing the WGSL source of the shaders loaded from files
+#[derive(Default)]
+pub struct ShaderEditor {
+    pub open: bool,
+    shader: Option<OpenShader>,
+}
+
+/// Colors the WGSL tokens of each line, and the background of the line with an error
+fn highlight(ui: &Ui, text: &str, error_line: Option<usize>) -> LayoutJob {
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let mut job = LayoutJob::default();
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let background = if error_line == Some(index + 1) {
+            ERROR_BACKGROUND
+        } else {
+            Color32::TRANSPARENT
+        };
+        let mut append = |token: &str, color: Color32| {
+            let format = TextFormat {
+                background,
+                ..TextFormat::simple(font_id.clone(), color)
+            };
+            job.append(token, 0.0, format);
+        };
+
+        if line.trim_start().starts_with('#') {
+            append(line, ATTRIBUTE_COLOR);
+            continue;
+        }
+        let mut rest = line;
+        while let Some(c) = rest.chars().next() {
+            let (length, color) = if rest.starts_with("//") {
+                (rest.trim_end_matches('\n').len(), COMMENT_COLOR)
+            } else if rest.starts_with("[[") {
+                let end = rest.find("]]").map_or(rest.len(), |end| end + 2);
+                (end, ATTRIBUTE_COLOR)
+            } else if c == '@' || c.is_alphabetic() || c == '_' {
+                let start = c.len_utf8();
+                let end = rest[start..]
+                    .find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .map_or(rest.len(), |end| end + start);
+                let word = &rest[..end];
+                let color = if c == '@' {
+                    ATTRIBUTE_COLOR
+                } else if KEYWORDS.contains(&word) {
+                    KEYWORD_COLOR
+                } else if TYPES.contains(&word)
+                    || word.starts_with("vec")
+                    || word.starts_with("mat")
+                    || word.starts_with("texture_")
+                {
+                    TYPE_COLOR
+                } else {
+                    TEXT_COLOR
+                };
+                (end, color)
+            } else if c.is_ascii_digit() {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '.')
+                    .unwrap_or(rest.len());
+                (end, NUMBER_COLOR)
+            } else {
+                (c.len_utf8(), TEXT_COLOR)
+            };
+            let (token, remaining) = rest.split_at(length);
+            append(token, color);
+            rest = remaining;
+        }
+    }
+    job
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn shader_editor_window(
+    mut egui_context: ResMut<EguiContext>,
+    mut editor: ResMut<ShaderEditor>,
+    mut shaders: ResMut<Assets<Shader>>,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    let ShaderEditor { open, shader } = &mut *editor;
+
+    // Apply the edits once the text stopped changing for a moment
+    if let Some(shader) = shader.as_mut() {
+        if shader.edited_at.map_or(false, |at| now - at >= APPLY_DELAY) {
+            shader.edited_at = None;
+            shader.apply(&mut shaders, now);
+        }
+    }
+
+    egui::Window::new("Shader editor")
+        .open(open)
+        .default_width(600.0)
+        .show(egui_context.ctx_mut(), |ui| {
+            let mut paths: Vec<_> = shaders
+                .ids()
+                .filter_map(|id| shader_path(&asset_server, &Handle::weak(id)))
+                .collect();
+            paths.sort();
+
+            ui.horizontal(|ui| {
+                let selected = shader.as_ref().map_or("", |shader| shader.path.as_str());
+                let mut opened = None;
+                egui::ComboBox::from_id_source("shader_editor_path")
+                    .selected_text(selected)
+                    .width(300.0)
+                    .show_ui(ui, |ui| {
+                        for path in paths {
+                            if ui.selectable_label(path == selected, &path).clicked() {
+                                opened = Some(path);
+                            }
+                        }
+                    });
+                if let Some(path) = opened {
+                    *shader = Some(OpenShader::open(path, &asset_server));
+                }
+
+                if let Some(shader) = shader.as_mut() {
+                    if ui
+                        .add_enabled(shader.unsaved, egui::Button::new("Save"))
+                        .clicked()
+                    {
+                        shader.save();
+                    }
+                    if ui.button("Revert").clicked() {
+                        *shader = OpenShader::open(shader.path.clone(), &asset_server);
+                        shader.edited_at = Some(now);
+                    }
+                }
+            });
+
+            let shader = if let Some(shader) = shader {
+                shader
+            } else {
+                ui.label("Select a shader to edit");
+                return;
+            };
+
+            if let Some(error) = &shader.error {
+                ui.label(
+                    RichText::new(format!(
+                        "{}:{}: {}",
+                        error.line, error.column, error.message
+                    ))
+                    .color(Color32::LIGHT_RED),
+                );
+            } else if shader.edited_at.is_some() {
+                ui.label("Editing...");
+            } else {
+                ui.label(RichText::new("Compiled").color(Color32::LIGHT_GREEN));
+            }
+
+            let error_line = shader.error.as_ref().map(|error| error.line);
+            let mut layouter = |ui: &Ui, text: &str, wrap_width: f32| {
+                let mut job = highlight(ui, text, error_line);
+                job.wrap.max_width = wrap_width;
+                ui.fonts().layout_job(job)
+            };
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                let response = ui.add(
+                    egui::TextEdit::multiline(&mut shader.text)
+                        .code_editor()
+                        .desired_rows(30)
+                        .desired_width(f32::INFINITY)
+                        .layouter(&mut layouter),
+                );
+                if response.changed() {
+                    shader.edited_at = Some(now);
+                    shader.unsaved = true;
+                }
+            });
+        });
+}
+
+/// Live WGSL editor, edits are applied to the shader asset as soon as they compile
+pub struct ShaderEditorPlugin;
+
+impl Plugin for ShaderEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ShaderEditor>()
+            .add_system(shader_editor_window);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::AssetPlugin;
+
+    use super::*;
+    use crate::shader_errors::process_shader;
+
+    const SHADER: &str = "
+        [[stage(fragment)]]
+        fn fragment() -> [[location(0)]] vec4<f32> {
+            var color = vec4<f32>(1.0, 0.0, 0.0, 1.0);
+        #ifdef VERTEX_TANGENTS
+            color = vec4<f32>(0.0, 1.0, 0.0, 1.0);
+        #endif
+            return color;
+        }
+    ";
+
+    #[test]
+    fn edit_breaking_the_tangents_branch_isnt_applied() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<Shader>();
+        let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+        let handle = shaders.add(Shader::from_wgsl(SHADER));
+
+        // The color without tangents is edited too, to see which version the asset has
+        let text = SHADER
+            .replace("(1.0, 0.0, 0.0, 1.0)", "(0.5, 0.5, 0.5, 1.0)")
+            .replace("(0.0, 1.0, 0.0, 1.0)", "(missing, 1.0, 0.0, 1.0)");
+        let mut shader = OpenShader {
+            path: "shaders/test.wgsl".to_owned(),
+            handle: handle.clone(),
+            text,
+            edited_at: None,
+            error: None,
+            unsaved: true,
+        };
+        shader.apply(&mut shaders, 0.0);
+        assert_eq!(shader.error.as_ref().map(|error| error.line), Some(6));
+        let source = process_shader(&handle, &shaders).unwrap();
+        assert!(source.contains("(1.0, 0.0, 0.0, 1.0)"), "{}", source);
+
+        shader.text = shader.text.replace("missing", "0.0");
+        shader.apply(&mut shaders, 1.0);
+        assert!(shader.error.is_none());
+        let source = process_shader(&handle, &shaders).unwrap();
+        assert!(source.contains("(0.5, 0.5, 0.5, 1.0)"), "{}", source);
+    }
+}
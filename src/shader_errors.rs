@@ -0,0 +1,471 @@
+use std::{fmt::Write as _, fs, path::PathBuf};
+
+use bevy::{
+    asset::{AssetStage, FileAssetIo, HandleId},
+    prelude::*,
+    render::render_resource::{ProcessShaderError, ShaderProcessor},
+    utils::{HashMap, HashSet},
+};
+use bevy_egui::{
+    egui::{self, Color32, RichText},
+    EguiContext,
+};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+/// Lines shown before and after the line of an error
+const SNIPPET_CONTEXT: usize = 2;
+
+/// Shader defs the mesh pipeline compiles the materials with, `VERTEX_TANGENTS` being set for
+/// the meshes with tangents. Each set is validated since an error can hide in an `#ifdef` branch.
+const SHADER_DEFS: &[&[&str]] = &[&[], &["VERTEX_TANGENTS"]];
+
+/// Validates shaders after they are loaded or hot reloaded, before the render world extracts them
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+struct ValidateShaders;
+
+/// A shader that failed to compile
+pub struct ShaderError {
+    /// Asset path of the shader
+    pub path: String,
+    /// 1-based line in the shader file, or in the processed shader if the error is in an import
+    pub line: usize,
+    /// 1-based column
+    pub column: usize,
+    /// The lines around the error, with line numbers and a marker under the column
+    pub snippet: String,
+    pub message: String,
+}
+
+/// Errors of the shaders loaded from files, and their last version that compiled
+#[derive(Default)]
+pub struct ShaderErrors {
+    errors: HashMap<HandleId, ShaderError>,
+    last_good: HashMap<HandleId, Shader>,
+    /// Shaders put back to their last good version, the `Modified` event it sends is ignored
+    reverted: HashSet<HandleId>,
+    /// Shaders with an import that wasn't loaded yet, checked again when another shader loads
+    pending: HashSet<HandleId>,
+    /// Materials whose uniform doesn't match their shader, found when their plugin is added
+    layout_errors: Vec<String>,
+}
+
+impl ShaderErrors {
+    pub fn iter(&self) -> impl Iterator<Item = &ShaderError> {
+        self.errors.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty() && self.layout_errors.is_empty()
+    }
+
+    /// Shows the diff between the uniform layout of a material and its shader until the app
+    /// is restarted, since the material reads garbage until both are fixed
+    pub fn add_layout_error(&mut self, diff: String) {
+        self.layout_errors.push(diff);
+    }
+
+    /// Validates a shader loaded from the file at `path`, keeping it as the last good version if
+    /// it compiles and putting the last good version back otherwise
+    fn check(&mut self, handle: &Handle<Shader>, path: &str, shaders: &mut Assets<Shader>) {
+        let shader = match shaders.get(handle) {
+            Some(shader) => shader,
+            None => return,
+        };
+
+        let file = fs::read_to_string(shader_file(path)).unwrap_or_default();
+        match check_shader(path, shader, shaders, &file) {
+            None => {
+                self.pending.insert(handle.id);
+            }
+            Some(Ok(())) => {
+                self.pending.remove(&handle.id);
+                if self.errors.remove(&handle.id).is_some() {
+                    info!("Shader {} compiles again", path);
+                }
+                self.last_good.insert(handle.id, shader.clone());
+            }
+            Some(Err(error)) => {
+                self.pending.remove(&handle.id);
+                error!(
+                    "Shader {}:{}:{}: {}",
+                    error.path, error.line, error.column, error.message
+                );
+                self.errors.insert(handle.id, error);
+                if let Some(shader) = self.last_good.get(&handle.id).cloned() {
+                    *shaders.get_mut(handle).unwrap() = shader;
+                    self.reverted.insert(handle.id);
+                }
+            }
+        }
+    }
+
+    /// Checks again the shaders whose imports weren't loaded, `path` gives their file
+    fn check_pending(
+        &mut self,
+        shaders: &mut Assets<Shader>,
+        path: impl Fn(&Handle<Shader>) -> Option<String>,
+    ) {
+        let pending: Vec<_> = self.pending.iter().copied().collect();
+        for id in pending {
+            let handle = Handle::weak(id);
+            match path(&handle) {
+                Some(path) => self.check(&handle, &path, shaders),
+                None => {
+                    self.pending.remove(&id);
+                }
+            }
+        }
+    }
+}
+
+/// Resolves the imports of a shader the same way the pipeline cache does before compiling it.
+/// Returns `None` if the shader or one of its imports isn't loaded yet.
+pub fn process_shader(handle: &Handle<Shader>, shaders: &Assets<Shader>) -> Option<String> {
+    try_process_shader(shaders.get(handle)?, shaders, &[])?.ok()
+}
+
+/// Like [`process_shader`] but keeps the preprocessor errors, apart from unresolved imports
+/// which usually mean the import is still loading
+fn try_process_shader(
+    shader: &Shader,
+    shaders: &Assets<Shader>,
+    shader_defs: &[String],
+) -> Option<Result<String, ProcessShaderError>> {
+    let mut all_shaders = HashMap::default();
+    let mut import_handles = HashMap::default();
+    for (id, shader) in shaders.iter() {
+        if let Some(import_path) = shader.import_path() {
+            import_handles.insert(import_path.clone(), Handle::weak(id));
+        }
+        all_shaders.insert(Handle::weak(id), shader.clone());
+    }
+
+    match ShaderProcessor::default().process(shader, shader_defs, &all_shaders, &import_handles) {
+        Ok(processed) => Some(Ok(processed.get_wgsl_source()?.to_owned())),
+        Err(ProcessShaderError::UnresolvedImport(_)) => None,
+        Err(err) => Some(Err(err)),
+    }
+}
+
+/// Path on disk of a shader loaded from `assets/`
+pub fn shader_file(path: &str) -> PathBuf {
+    FileAssetIo::get_root_path().join("assets").join(path)
+}
+
+/// Asset path of a shader loaded from a file, `None` for the shaders embedded in bevy
+pub fn shader_path(asset_server: &AssetServer, handle: &Handle<Shader>) -> Option<String> {
+    let path = asset_server.get_handle_path(handle)?;
+    Some(path.path().to_string_lossy().replace('\\', "/"))
+}
+
+/// Resolves the imports of a shader then parses and validates it with naga, once for each set
+/// of [`SHADER_DEFS`]. The first error found is returned.
+/// `file` is the text the shader was created from, errors are reported at their line in it.
+/// Returns `None` if one of the imports isn't loaded yet.
+pub fn check_shader(
+    path: &str,
+    shader: &Shader,
+    shaders: &Assets<Shader>,
+    file: &str,
+) -> Option<Result<(), ShaderError>> {
+    for defs in SHADER_DEFS {
+        let shader_defs: Vec<_> = defs.iter().map(ToString::to_string).collect();
+        let mut error = match try_process_shader(shader, shaders, &shader_defs)? {
+            Ok(source) => match validate(&source) {
+                Ok(()) => continue,
+                Err((message, location)) => {
+                    ShaderError::new(path.to_owned(), &source, file, message, location)
+                }
+            },
+            Err(err) => ShaderError::new(path.to_owned(), "", file, err.to_string(), None),
+        };
+        if !defs.is_empty() {
+            error.message = format!("{} (with {})", error.message, defs.join(", "));
+        }
+        return Some(Err(error));
+    }
+    Some(Ok(()))
+}
+
+/// Parses and validates a processed WGSL shader.
+/// On failure returns the message and the 1-based line and column of the error, if known.
+fn validate(source: &str) -> Result<(), (String, Option<(usize, usize)>)> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|err| (err.to_string(), Some(err.location(source))))?;
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|err| {
+            let location = err
+                .spans()
+                .find_map(|(span, _)| span.to_range())
+                .map(|range| line_column(source, range.start));
+            (err.into_inner().to_string(), location)
+        })?;
+    Ok(())
+}
+
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}
+
+/// Finds the line of the file the processed line comes from, comparing the lines around it
+/// since the preprocessor removes and inserts lines
+fn file_line(file: &[&str], processed: &[&str], line: usize) -> Option<usize> {
+    fn around<'a>(lines: &[&'a str], center: usize, i: usize) -> Option<&'a str> {
+        (center + i)
+            .checked_sub(SNIPPET_CONTEXT)
+            .and_then(|i| lines.get(i).map(|line| line.trim()))
+    }
+
+    let text = processed.get(line)?.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let score = |candidate: usize| {
+        (0..=2 * SNIPPET_CONTEXT)
+            .filter(
+                |&i| match (around(file, candidate, i), around(processed, line, i)) {
+                    (Some(file), Some(processed)) => file == processed,
+                    _ => false,
+                },
+            )
+            .count()
+    };
+    file.iter()
+        .enumerate()
+        .filter(|(_, file_line)| file_line.trim() == text)
+        .map(|(candidate, _)| candidate)
+        .max_by_key(|&candidate| (score(candidate), std::cmp::Reverse(candidate)))
+}
+
+impl ShaderError {
+    fn new(
+        path: String,
+        source: &str,
+        file: &str,
+        message: String,
+        location: Option<(usize, usize)>,
+    ) -> Self {
+        let (line, column) = location.unwrap_or((1, 1));
+        let processed: Vec<_> = source.lines().collect();
+        let file: Vec<_> = file.lines().collect();
+
+        // Show the line in the file when it can be found, the processed shader otherwise
+        let (lines, index) = match file_line(&file, &processed, line - 1) {
+            Some(index) => (&file, index),
+            None => (&processed, line - 1),
+        };
+        let mut snippet = String::new();
+        if location.is_some() {
+            let first = index.saturating_sub(SNIPPET_CONTEXT);
+            let last = (index + SNIPPET_CONTEXT).min(lines.len().saturating_sub(1));
+            let width = (last + 1).to_string().len();
+            for (i, text) in lines.iter().enumerate().take(last + 1).skip(first) {
+                writeln!(snippet, "{:>width$} | {}", i + 1, text, width = width).unwrap();
+                if i == index {
+                    writeln!(
+                        snippet,
+                        "{:>width$} | {:>column$}",
+                        "",
+                        "^",
+                        width = width,
+                        column = column
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        Self {
+            path,
+            line: index + 1,
+            column,
+            snippet,
+            message,
+        }
+    }
+}
+
+/// Validates the shaders loaded from files when they are created or modified.
+/// A shader that fails is put back to its last good version so the pipelines using it keep working.
+/// Shaders whose imports aren't loaded yet are validated once another shader loads.
+#[allow(clippy::needless_pass_by_value)]
+fn validate_shaders(
+    mut events: EventReader<AssetEvent<Shader>>,
+    mut shaders: ResMut<Assets<Shader>>,
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<ShaderErrors>,
+) {
+    let mut loaded = false;
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { handle } => {
+                errors.errors.remove(&handle.id);
+                errors.last_good.remove(&handle.id);
+                errors.pending.remove(&handle.id);
+                continue;
+            }
+        };
+        if errors.reverted.remove(&handle.id) {
+            continue;
+        }
+        // Any shader can be an import, including the ones embedded in bevy
+        loaded = true;
+        // Shaders embedded in bevy have no path and are assumed to be valid
+        if let Some(path) = shader_path(&asset_server, handle) {
+            errors.check(handle, &path, &mut shaders);
+        }
+    }
+    if loaded {
+        errors.check_pending(&mut shaders, |handle| shader_path(&asset_server, handle));
+    }
+}
+
+/// Window listing the shader errors, shown until they're all fixed
+#[allow(clippy::needless_pass_by_value)]
+fn shader_errors_window(mut egui_context: ResMut<EguiContext>, errors: Res<ShaderErrors>) {
+    if errors.is_empty() {
+        return;
+    }
+    egui::Window::new("Shader errors")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 10.0])
+        .resizable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            for error in errors.iter() {
+                ui.label(
+                    RichText::new(format!("{}:{}:{}", error.path, error.line, error.column))
+                        .strong(),
+                );
+                ui.label(RichText::new(&error.message).color(Color32::LIGHT_RED));
+                if !error.snippet.is_empty() {
+                    ui.label(RichText::new(&error.snippet).monospace());
+                }
+                ui.separator();
+            }
+            if !errors.errors.is_empty() {
+                ui.label("The last version that compiled is used until the errors are fixed");
+            }
+            if !errors.layout_errors.is_empty() {
+                if !errors.errors.is_empty() {
+                    ui.separator();
+                }
+                for diff in &errors.layout_errors {
+                    ui.label(RichText::new(diff).monospace().color(Color32::LIGHT_RED));
+                    ui.separator();
+                }
+                ui.label("Restart after fixing the uniform layouts");
+            }
+        });
+}
+
+/// Shows the errors of hot reloaded shaders instead of letting the pipelines using them fail
+pub struct ShaderErrorsPlugin;
+
+impl Plugin for ShaderErrorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ShaderErrors>()
+            .add_stage_after(
+                AssetStage::AssetEvents,
+                ValidateShaders,
+                SystemStage::single(validate_shaders),
+            )
+            .add_system(shader_errors_window);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::AssetPlugin;
+
+    use super::*;
+
+    const IMPORTING: &str = "
+        #import playground::test_import
+
+        [[stage(fragment)]]
+        fn fragment() -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(imported(), 0.0, 0.0, 1.0);
+        }
+    ";
+
+    const IMPORT: &str = "
+        #define_import_path playground::test_import
+
+        fn imported() -> f32 {
+            return 1.0;
+        }
+    ";
+
+    #[test]
+    fn every_shader_def_set_is_checked() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<Shader>();
+        let shaders = app.world.resource::<Assets<Shader>>();
+
+        // The error is only in the branch used by the meshes with tangents
+        let file = "
+            [[stage(fragment)]]
+            fn fragment() -> [[location(0)]] vec4<f32> {
+                var color = vec4<f32>(1.0, 0.0, 0.0, 1.0);
+            #ifdef VERTEX_TANGENTS
+                color = vec4<f32>(missing, 0.0, 0.0, 1.0);
+            #endif
+                return color;
+            }
+        ";
+        let shader = Shader::from_wgsl(file);
+        let error = match check_shader("shaders/test.wgsl", &shader, shaders, file) {
+            Some(Err(error)) => error,
+            _ => panic!("the error with VERTEX_TANGENTS isn't reported"),
+        };
+        assert_eq!(error.line, 6);
+        assert!(
+            error.message.contains("VERTEX_TANGENTS"),
+            "{}",
+            error.message
+        );
+
+        let fixed = file.replace("missing", "1.0");
+        let shader = Shader::from_wgsl(fixed.clone());
+        assert!(matches!(
+            check_shader("shaders/test.wgsl", &shader, shaders, &fixed),
+            Some(Ok(()))
+        ));
+    }
+
+    #[test]
+    fn shader_is_checked_once_its_import_loads() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<Shader>();
+        let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+        let mut errors = ShaderErrors::default();
+        let path = |_: &Handle<Shader>| Some("shaders/test.wgsl".to_owned());
+
+        let importing = shaders.add(Shader::from_wgsl(IMPORTING));
+        errors.check(&importing, "shaders/test.wgsl", &mut shaders);
+        assert!(errors.pending.contains(&importing.id));
+        assert!(!errors.last_good.contains_key(&importing.id));
+
+        shaders.add(Shader::from_wgsl(IMPORT));
+        errors.check_pending(&mut shaders, path);
+        assert!(errors.pending.is_empty());
+        assert!(errors.is_empty());
+        assert!(errors.last_good.contains_key(&importing.id));
+
+        // A broken version is reverted to the one that compiled
+        let broken = IMPORTING.replace("imported()", "missing()");
+        *shaders.get_mut(&importing).unwrap() = Shader::from_wgsl(broken);
+        errors.check(&importing, "shaders/test.wgsl", &mut shaders);
+        assert_eq!(errors.iter().count(), 1);
+        let source = process_shader(&importing, &shaders).unwrap();
+        assert!(source.contains("imported()"), "{}", source);
+    }
+}
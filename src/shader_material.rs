@@ -0,0 +1,435 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    core_pipeline::{AlphaMask3d, Opaque3d, Transparent3d},
+    pbr::{
+        AlphaMode, DrawMesh, Material, MaterialPipeline, MeshPipeline, MeshPipelineKey,
+        MeshUniform, SetMaterialBindGroup, SetMeshBindGroup, SetMeshViewBindGroup,
+    },
+    prelude::*,
+    render::{
+        mesh::MeshVertexBufferLayout,
+        render_asset::{PrepareAssetLabel, RenderAssetPlugin, RenderAssets},
+        render_component::ExtractComponentPlugin,
+        render_phase::{AddRenderCommand, DrawFunctions, RenderPhase, SetItemPipeline},
+        render_resource::{
+            BindGroupLayout, Buffer, PipelineCache, RenderPipelineDescriptor,
+            SpecializedMeshPipeline, SpecializedMeshPipelineError, SpecializedMeshPipelines,
+        },
+        renderer::RenderQueue,
+        view::{ExtractedView, VisibleEntities},
+        RenderApp, RenderStage,
+    },
+    utils::HashMap,
+};
+
+use crate::{
+    globals::{GlobalsMeta, SetGlobalsBindGroup},
+    inspector::{Inspect, InspectorRegistry},
+    layout_check::{self, MaterialLayout},
+    models::spawn_model_meshes,
+    presets::Preset,
+    shader_errors::ShaderErrors,
+};
+
+/// Keeps `shaders/vertex.wgsl` loaded so the material shaders can `#import playground::vertex`
+struct VertexShader(#[allow(dead_code)] Handle<Shader>);
+
+impl FromWorld for VertexShader {
+    fn from_world(world: &mut World) -> Self {
+        Self(world.resource::<AssetServer>().load("shaders/vertex.wgsl"))
+    }
+}
+
+/// Implemented by `#[derive(ShaderMaterial)]`, lets [`ShaderMaterialPlugin`] update the
+/// uniform buffer of a material in place when only its uniform values changed.
+pub trait ShaderMaterial: Material + Inspect {
+    /// The std140 bytes of the uniform data, empty if the material has no uniform
+    fn uniform_bytes(&self) -> Vec<u8>;
+    /// Weak handles of every texture binding, in binding order
+    fn textures(&self) -> Vec<Option<Handle<Image>>>;
+    /// The uniform buffer of a prepared material
+    fn gpu_buffer(prepared: &Self::PreparedAsset) -> Option<&Buffer>;
+    /// The texture handles a prepared material bind group was created with
+    fn gpu_textures(prepared: &Self::PreparedAsset) -> &[Option<Handle<Image>>];
+    /// Whether the buffer and bind group of a prepared material can be reused with the new
+    /// uniform `bytes` and `textures`
+    fn can_update_in_place(
+        prepared: &Self::PreparedAsset,
+        _bytes: &[u8],
+        textures: &[Option<Handle<Image>>],
+    ) -> bool {
+        Self::gpu_textures(prepared) == textures
+    }
+    /// Shader replacing the one returned by `Material::vertex_shader` and
+    /// `Material::fragment_shader`, for materials choosing their shader at runtime
+    fn gpu_shader(_prepared: &Self::PreparedAsset) -> Option<&Handle<Shader>> {
+        None
+    }
+    /// The shader and Rust layout of the uniform, `None` when it's only known at runtime
+    fn uniform_layout() -> Option<MaterialLayout> {
+        None
+    }
+}
+
+/// Adds a material deriving `ShaderMaterial` to the app.
+///
+/// This works like [`MaterialPlugin`](bevy::pbr::MaterialPlugin), but also binds the
+/// [`Globals`](crate::globals::Globals) at `group(3)`, so `GlobalsPlugin` needs to be added first.
+/// It also reuses the buffer and bind group of a modified material instead of recreating them,
+/// as long as its textures didn't change.
+/// The material is added to the inspector panel, with its presets saved to `assets/presets/`.
+/// The meshes of the [`Model`](crate::models::Model)s using it are spawned once loaded,
+/// which needs `ModelPlugin`.
+pub struct ShaderMaterialPlugin<M: ShaderMaterial>(PhantomData<M>);
+
+impl<M: ShaderMaterial> Default for ShaderMaterialPlugin<M> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<M: ShaderMaterial + Preset> Plugin for ShaderMaterialPlugin<M> {
+    fn build(&self, app: &mut App) {
+        // The mismatch stays on screen in the shader errors window, which needs
+        // `ShaderErrorsPlugin` to be added first
+        if let Err(diff) = layout_check::check::<M>() {
+            error!("{}", diff);
+            if let Some(mut errors) = app.world.get_resource_mut::<ShaderErrors>() {
+                errors.add_layout_error(diff);
+            }
+        }
+
+        app.init_resource::<VertexShader>()
+            .init_resource::<InspectorRegistry>();
+        app.world
+            .resource_mut::<InspectorRegistry>()
+            .register::<M>();
+        app.add_asset::<M>()
+            .add_plugin(ExtractComponentPlugin::<Handle<M>>::default())
+            .add_plugin(RenderAssetPlugin::<M>::default())
+            .add_system(spawn_model_meshes::<M>);
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .add_render_command::<Transparent3d, DrawShaderMaterial<M>>()
+                .add_render_command::<Opaque3d, DrawShaderMaterial<M>>()
+                .add_render_command::<AlphaMask3d, DrawShaderMaterial<M>>()
+                .init_resource::<MaterialPipeline<M>>()
+                .init_resource::<ShaderMaterialPipeline<M>>()
+                .init_resource::<SpecializedMeshPipelines<ShaderMaterialPipeline<M>>>()
+                .add_system_to_stage(RenderStage::Queue, queue_shader_material_meshes::<M>)
+                .init_resource::<ExtractedMaterialUniforms<M>>()
+                .init_resource::<UpdatedMaterials<M>>()
+                .add_system_to_stage(RenderStage::Extract, extract_material_uniforms::<M>)
+                .add_system_to_stage(
+                    RenderStage::Prepare,
+                    update_material_uniforms::<M>.label(PrepareAssetLabel::PreAssetPrepare),
+                )
+                .add_system_to_stage(
+                    RenderStage::Prepare,
+                    clear_updated_materials::<M>.after(PrepareAssetLabel::AssetPrepare),
+                );
+        }
+    }
+}
+
+struct ExtractedMaterialUniform<M: ShaderMaterial> {
+    handle: Handle<M>,
+    bytes: Vec<u8>,
+    textures: Vec<Option<Handle<Image>>>,
+}
+
+/// The uniform data of every material modified this frame
+pub struct ExtractedMaterialUniforms<M: ShaderMaterial> {
+    uniforms: Vec<ExtractedMaterialUniform<M>>,
+}
+
+impl<M: ShaderMaterial> Default for ExtractedMaterialUniforms<M> {
+    fn default() -> Self {
+        Self {
+            uniforms: Vec::new(),
+        }
+    }
+}
+
+/// The uniform bytes and the textures of a material
+type MaterialContent = (Vec<u8>, Vec<Option<Handle<Image>>>);
+
+/// Prepared materials whose buffer was already updated this frame.
+///
+/// They are indexed by their content, so `prepare_asset` can pick them back up
+/// without knowing the handle of the material it prepares.
+pub struct UpdatedMaterials<M: ShaderMaterial> {
+    materials: HashMap<MaterialContent, Vec<M::PreparedAsset>>,
+}
+
+impl<M: ShaderMaterial> Default for UpdatedMaterials<M> {
+    fn default() -> Self {
+        Self {
+            materials: HashMap::default(),
+        }
+    }
+}
+
+impl<M: ShaderMaterial> UpdatedMaterials<M> {
+    /// Takes an already updated prepared material matching `material`, if any
+    pub fn take(&mut self, material: &M) -> Option<M::PreparedAsset> {
+        self.materials
+            .get_mut(&(material.uniform_bytes(), material.textures()))
+            .and_then(Vec::pop)
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn extract_material_uniforms<M: ShaderMaterial>(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<M>>,
+    assets: Res<Assets<M>>,
+) {
+    let mut modified = Vec::new();
+    for event in events.iter() {
+        match event {
+            AssetEvent::Modified { handle } => modified.push(handle),
+            AssetEvent::Removed { handle } => modified.retain(|h| *h != handle),
+            AssetEvent::Created { .. } => {}
+        }
+    }
+
+    let uniforms = modified
+        .into_iter()
+        .filter_map(|handle| {
+            let material = assets.get(handle)?;
+            Some(ExtractedMaterialUniform {
+                handle: handle.clone_weak(),
+                bytes: material.uniform_bytes(),
+                textures: material.textures(),
+            })
+        })
+        .collect();
+
+    commands.insert_resource(ExtractedMaterialUniforms::<M> { uniforms });
+}
+
+/// Writes the new uniform values to the existing buffers and moves the prepared materials
+/// to [`UpdatedMaterials`], they will be put back by `prepare_asset` right after.
+#[allow(clippy::needless_pass_by_value)]
+fn update_material_uniforms<M: ShaderMaterial>(
+    mut extracted: ResMut<ExtractedMaterialUniforms<M>>,
+    mut render_materials: ResMut<RenderAssets<M>>,
+    mut updated: ResMut<UpdatedMaterials<M>>,
+    render_queue: Res<RenderQueue>,
+) {
+    for ExtractedMaterialUniform {
+        handle,
+        bytes,
+        textures,
+    } in extracted.uniforms.drain(..)
+    {
+        let can_update = match render_materials.get(&handle) {
+            Some(prepared) => M::can_update_in_place(prepared, &bytes, &textures),
+            None => false,
+        };
+        if !can_update {
+            // The bind group needs to be recreated
+            continue;
+        }
+
+        let prepared = render_materials.remove(&handle).unwrap();
+        if let Some(buffer) = M::gpu_buffer(&prepared) {
+            render_queue.write_buffer(buffer, 0, &bytes);
+        }
+        updated
+            .materials
+            .entry((bytes, textures))
+            .or_default()
+            .push(prepared);
+    }
+}
+
+fn clear_updated_materials<M: ShaderMaterial>(mut updated: ResMut<UpdatedMaterials<M>>) {
+    updated.materials.clear();
+}
+
+/// The [`MaterialPipeline`] with the [`Globals`](crate::globals::Globals) layout appended
+pub struct ShaderMaterialPipeline<M: ShaderMaterial> {
+    pub mesh_pipeline: MeshPipeline,
+    pub material_layout: BindGroupLayout,
+    pub globals_layout: BindGroupLayout,
+    pub vertex_shader: Option<Handle<Shader>>,
+    pub fragment_shader: Option<Handle<Shader>>,
+    marker: PhantomData<M>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ShaderMaterialKey {
+    pub mesh: MeshPipelineKey,
+    /// See [`ShaderMaterial::gpu_shader`]
+    pub shader: Option<Handle<Shader>>,
+}
+
+impl<M: ShaderMaterial> SpecializedMeshPipeline for ShaderMaterialPipeline<M> {
+    type Key = ShaderMaterialKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key.mesh, layout)?;
+        let vertex_shader = key.shader.as_ref().or(self.vertex_shader.as_ref());
+        if let Some(vertex_shader) = vertex_shader {
+            descriptor.vertex.shader = vertex_shader.clone();
+        }
+        let fragment_shader = key.shader.as_ref().or(self.fragment_shader.as_ref());
+        if let Some(fragment_shader) = fragment_shader {
+            descriptor.fragment.as_mut().unwrap().shader = fragment_shader.clone();
+        }
+
+        // MeshPipeline::specialize always returns the view and mesh layouts
+        let descriptor_layout = descriptor.layout.as_mut().unwrap();
+        descriptor_layout.insert(1, self.material_layout.clone());
+        descriptor_layout.push(self.globals_layout.clone());
+        Ok(descriptor)
+    }
+}
+
+impl<M: ShaderMaterial> FromWorld for ShaderMaterialPipeline<M> {
+    fn from_world(world: &mut World) -> Self {
+        let material_pipeline = world.resource::<MaterialPipeline<M>>();
+        let globals_layout = world
+            .get_resource::<GlobalsMeta>()
+            .expect("GlobalsPlugin needs to be added before ShaderMaterialPlugin")
+            .layout
+            .clone();
+
+        ShaderMaterialPipeline {
+            mesh_pipeline: material_pipeline.mesh_pipeline.clone(),
+            material_layout: material_pipeline.material_layout.clone(),
+            globals_layout,
+            vertex_shader: material_pipeline.vertex_shader.clone(),
+            fragment_shader: material_pipeline.fragment_shader.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+type DrawShaderMaterial<M> = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMaterialBindGroup<M, 1>,
+    SetMeshBindGroup<2>,
+    SetGlobalsBindGroup<3>,
+    DrawMesh,
+);
+
+/// Same as bevy's `queue_material_meshes` but with the [`ShaderMaterialPipeline`]
+#[allow(
+    clippy::too_many_arguments,
+    clippy::needless_pass_by_value,
+    clippy::type_complexity
+)]
+fn queue_shader_material_meshes<M: ShaderMaterial>(
+    opaque_draw_functions: Res<DrawFunctions<Opaque3d>>,
+    alpha_mask_draw_functions: Res<DrawFunctions<AlphaMask3d>>,
+    transparent_draw_functions: Res<DrawFunctions<Transparent3d>>,
+    material_pipeline: Res<ShaderMaterialPipeline<M>>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<ShaderMaterialPipeline<M>>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    msaa: Res<Msaa>,
+    render_meshes: Res<RenderAssets<Mesh>>,
+    render_materials: Res<RenderAssets<M>>,
+    material_meshes: Query<(&Handle<M>, &Handle<Mesh>, &MeshUniform)>,
+    mut views: Query<(
+        &ExtractedView,
+        &VisibleEntities,
+        &mut RenderPhase<Opaque3d>,
+        &mut RenderPhase<AlphaMask3d>,
+        &mut RenderPhase<Transparent3d>,
+    )>,
+) {
+    let draw_opaque = opaque_draw_functions
+        .read()
+        .get_id::<DrawShaderMaterial<M>>()
+        .unwrap();
+    let draw_alpha_mask = alpha_mask_draw_functions
+        .read()
+        .get_id::<DrawShaderMaterial<M>>()
+        .unwrap();
+    let draw_transparent = transparent_draw_functions
+        .read()
+        .get_id::<DrawShaderMaterial<M>>()
+        .unwrap();
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples);
+
+    for (view, visible_entities, mut opaque_phase, mut alpha_mask_phase, mut transparent_phase) in
+        views.iter_mut()
+    {
+        let inverse_view_row_2 = view.transform.compute_matrix().inverse().row(2);
+
+        for visible_entity in &visible_entities.entities {
+            let (material_handle, mesh_handle, mesh_uniform) =
+                match material_meshes.get(*visible_entity) {
+                    Ok(query_item) => query_item,
+                    Err(_) => continue,
+                };
+            let (material, mesh) = match (
+                render_materials.get(material_handle),
+                render_meshes.get(mesh_handle),
+            ) {
+                (Some(material), Some(mesh)) => (material, mesh),
+                _ => continue,
+            };
+
+            let mut mesh_key =
+                MeshPipelineKey::from_primitive_topology(mesh.primitive_topology) | msaa_key;
+            let alpha_mode = M::alpha_mode(material);
+            if let AlphaMode::Blend = alpha_mode {
+                mesh_key |= MeshPipelineKey::TRANSPARENT_MAIN_PASS;
+            }
+
+            let key = ShaderMaterialKey {
+                mesh: mesh_key,
+                shader: M::gpu_shader(material).map(Handle::clone_weak),
+            };
+            let pipeline = match pipelines.specialize(
+                &mut pipeline_cache,
+                &material_pipeline,
+                key,
+                &mesh.layout,
+            ) {
+                Ok(id) => id,
+                Err(err) => {
+                    error!("{}", err);
+                    continue;
+                }
+            };
+
+            // z component of the mesh translation in view space
+            let mesh_z = inverse_view_row_2.dot(mesh_uniform.transform.col(3));
+            let entity = *visible_entity;
+            match alpha_mode {
+                AlphaMode::Opaque => opaque_phase.add(Opaque3d {
+                    entity,
+                    draw_function: draw_opaque,
+                    pipeline,
+                    // front to back
+                    distance: -mesh_z,
+                }),
+                AlphaMode::Mask(_) => alpha_mask_phase.add(AlphaMask3d {
+                    entity,
+                    draw_function: draw_alpha_mask,
+                    pipeline,
+                    // front to back
+                    distance: -mesh_z,
+                }),
+                AlphaMode::Blend => transparent_phase.add(Transparent3d {
+                    entity,
+                    draw_function: draw_transparent,
+                    pipeline,
+                    // back to front
+                    distance: mesh_z,
+                }),
+            }
+        }
+    }
+}
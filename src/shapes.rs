@@ -1,118 +1,1207 @@
-use bevy::{
-    math::Vec3,
-    prelude::Mesh,
-    render::{mesh::Indices, render_resource::PrimitiveTopology},
-};
-
-/// A cylinder which stands on the XZ plane
-pub struct Cylinder {
-    /// Radius of the cylinder (X&Z axis)
-    pub radius: f32,
-    /// Height of the cylinder (Y axis)
-    pub height: f32,
-    /// Number of vertices around each horizontal slice of the cylinder
-    pub resolution: u32,
-    /// Number of vertical subdivisionss
-    pub subdivisions: u32,
-}
-
-impl Default for Cylinder {
-    fn default() -> Self {
-        Self {
-            radius: 0.5,
-            height: 1.0,
-            resolution: 20,
-            subdivisions: 4,
-        }
-    }
-}
-
-impl From<Cylinder> for Mesh {
-    fn from(c: Cylinder) -> Self {
-        assert!(c.radius > 0.0 && c.height > 0.0 && c.resolution > 0 && c.subdivisions > 0);
-
-        let count = (c.resolution * (c.subdivisions + 3) + 2) as usize;
-        let mut positions = Vec::with_capacity(count);
-        let step = std::f32::consts::PI * 2.0 / c.resolution as f32;
-        let mut add_ring = |height, with_center| {
-            if with_center {
-                positions.push([0.0, height, 0.0]);
-            }
-            for j in 0..c.resolution {
-                let theta = step * j as f32;
-                positions.push([theta.cos() * c.radius, height, theta.sin() * c.radius]);
-            }
-        };
-
-        // Shaft vertices
-        let h_step = c.height / c.subdivisions as f32;
-        for i in 0..=c.subdivisions {
-            add_ring(c.height * 0.5 - h_step * i as f32, false);
-        }
-
-        // Top vertices
-        let top_offset = c.resolution * (c.subdivisions + 1);
-        add_ring(c.height * 0.5, true);
-
-        // Bottom vertices
-        let bottom_offset = top_offset + c.resolution + 1;
-        add_ring(-c.height * 0.5, true);
-        assert_eq!(positions.len(), count);
-
-        let index_count = ((6 * c.subdivisions * c.resolution) + 6 * c.resolution) as usize;
-        let mut indices = Vec::with_capacity(index_count);
-
-        // Shaft quads
-        for i in 0..c.subdivisions {
-            let base1 = c.resolution * i;
-            let base2 = base1 + c.resolution;
-            for j in 0..c.resolution {
-                let j1 = (j + 1) % c.resolution;
-                indices.extend([base1 + j, base1 + j1, base2 + j].iter().copied());
-                indices.extend([base1 + j1, base2 + j1, base2 + j].iter().copied());
-            }
-        }
-
-        // Top circle
-        for j in 0..c.resolution {
-            let j1 = (j + 1) % c.resolution;
-            let base = top_offset + 1;
-            indices.extend([base + j1, base + j, top_offset].iter().copied());
-        }
-        // Bottom circle
-        for j in 0..c.resolution {
-            let j1 = (j + 1) % c.resolution;
-            let base = bottom_offset + 1;
-            indices.extend([base + j, base + j1, bottom_offset].iter().copied());
-        }
-        assert_eq!(indices.len(), index_count);
-
-        let mut normals: Vec<[f32; 3]> = positions
-            .iter()
-            .map(|&p| {
-                (Vec3::from(p) * Vec3::new(1.0, 0.0, 1.0))
-                    .normalize()
-                    .into()
-            })
-            .collect();
-
-        for i in top_offset..bottom_offset {
-            normals[i as usize] = [0.0, 1.0, 0.0];
-        }
-        for i in bottom_offset..count as u32 {
-            normals[i as usize] = [0.0, -1.0, 0.0];
-        }
-
-        let uvs: Vec<[f32; 2]> = positions
-            .iter()
-            .map(|&p| [p[0] / c.radius, (p[1] + c.height) / (c.height * 2.0)])
-            .collect();
-
-        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        mesh.set_indices(Some(Indices::U32(indices)));
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-        mesh
-    }
-}
+use std::{
+    f32::consts::{FRAC_PI_2, PI, TAU},
+    fmt::Display,
+};
+
+use bevy::{
+    log::warn,
+    math::{Vec2, Vec3},
+    prelude::Mesh,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    utils::HashMap,
+};
+use serde::Deserialize;
+
+/// Smallest radius or size of a shape
+const MIN_SIZE: f32 = 1e-3;
+
+/// Smallest number of vertices around a shape
+const MIN_RESOLUTION: u32 = 3;
+
+/// Most subdivisions of an [`Icosphere`], each one multiplies its triangles by 4
+const MAX_ICOSPHERE_SUBDIVISIONS: u32 = 7;
+
+/// `value`, or `min` with a warning if it's smaller or NaN.
+/// The shapes come from a scene file, invalid settings are fixed rather than panicking on reload.
+fn at_least<T: PartialOrd + Display + Copy>(setting: &str, value: T, min: T) -> T {
+    if value >= min {
+        value
+    } else {
+        warn!("Invalid {} {}, using {}", setting, value, min);
+        min
+    }
+}
+
+/// `value`, or `max` with a warning if it's larger
+fn at_most<T: PartialOrd + Display + Copy>(setting: &str, value: T, max: T) -> T {
+    if value <= max {
+        value
+    } else {
+        warn!("Invalid {} {}, using {}", setting, value, max);
+        max
+    }
+}
+
+/// A cylinder which stands on the XZ plane
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Cylinder {
+    /// Radius of the cylinder (X&Z axis)
+    pub radius: f32,
+    /// Height of the cylinder (Y axis)
+    pub height: f32,
+    /// Number of vertices around each horizontal slice of the cylinder
+    pub resolution: u32,
+    /// Number of vertical subdivisionss
+    pub subdivisions: u32,
+}
+
+impl Default for Cylinder {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            height: 1.0,
+            resolution: 20,
+            subdivisions: 4,
+        }
+    }
+}
+
+impl From<Cylinder> for Mesh {
+    fn from(mut c: Cylinder) -> Self {
+        c.radius = at_least("cylinder radius", c.radius, MIN_SIZE);
+        c.height = at_least("cylinder height", c.height, MIN_SIZE);
+        c.resolution = at_least("cylinder resolution", c.resolution, MIN_RESOLUTION);
+        c.subdivisions = at_least("cylinder subdivisions", c.subdivisions, 1);
+
+        // Shaft, U goes around the cylinder and V from the top to the bottom
+        let mut buffers = MeshBuffers::default();
+        let (radius, height) = (c.radius, c.height);
+        ParametricSurface::new(|u, v| {
+            let (sin, cos) = (TAU * u).sin_cos();
+            Vec3::new(cos * radius, height * (0.5 - v), sin * radius)
+        })
+        .with_normal(|u, _| {
+            let (sin, cos) = (TAU * u).sin_cos();
+            Vec3::new(cos, 0.0, sin)
+        })
+        .with_resolution(c.resolution, c.subdivisions)
+        .with_wrapping(true, false)
+        .build(&mut buffers);
+
+        // Caps
+        buffers.disc(c.radius, c.height * 0.5, c.resolution, true);
+        buffers.disc(c.radius, -c.height * 0.5, c.resolution, false);
+        buffers.into()
+    }
+}
+
+/// Step in U and V of the finite differences giving the normals of a [`ParametricSurface`]
+const NORMAL_EPSILON: f32 = 1e-3;
+
+/// A surface given by a function of `(u, v)` in `[0, 1]`, like a Klein bottle or a superquadric.
+///
+/// The UVs of the mesh are `(u, v)`. The triangles face the side of
+/// `d(position)/du x d(position)/dv`, which is also the side of the normals computed by
+/// finite differences.
+pub struct ParametricSurface<'a> {
+    position: Box<dyn Fn(f32, f32) -> Vec3 + 'a>,
+    normal: Option<Box<dyn Fn(f32, f32) -> Vec3 + 'a>>,
+    /// Number of subdivisions along U and V
+    resolution: (u32, u32),
+    /// Whether the surface is closed along U and V, with `f(0, v) == f(1, v)`
+    /// or `f(u, 0) == f(u, 1)`
+    wrap: (bool, bool),
+}
+
+impl<'a> ParametricSurface<'a> {
+    /// A surface with normals computed by finite differences, 32 by 32 subdivisions and no wrapping
+    pub fn new(position: impl Fn(f32, f32) -> Vec3 + 'a) -> Self {
+        Self {
+            position: Box::new(position),
+            normal: None,
+            resolution: (32, 32),
+            wrap: (false, false),
+        }
+    }
+
+    /// Uses the normals given by `normal` instead of finite differences
+    #[must_use]
+    pub fn with_normal(mut self, normal: impl Fn(f32, f32) -> Vec3 + 'a) -> Self {
+        self.normal = Some(Box::new(normal));
+        self
+    }
+
+    #[must_use]
+    pub fn with_resolution(mut self, u: u32, v: u32) -> Self {
+        self.resolution = (u, v);
+        self
+    }
+
+    /// Closes the surface along U and/or V: the last column or row of vertices gets the
+    /// positions and normals of the first one so the seam has no crack,
+    /// and the finite differences wrap around it
+    #[must_use]
+    pub fn with_wrapping(mut self, u: bool, v: bool) -> Self {
+        self.wrap = (u, v);
+        self
+    }
+
+    /// A superellipsoid, `exponents` are the east-west and north-south exponents:
+    /// 1 gives a sphere, close to 0 a cube and 2 an octahedron
+    pub fn superellipsoid(radius: f32, exponents: Vec2) -> Self {
+        // Signed power, keeps the sign of the base. Small bases are rounded to zero since cos(PI / 2)
+        // isn't exactly zero, and a small exponent would take it far from it
+        let power = |x: f32, exponent: f32| {
+            if x.abs() < 1e-6 {
+                0.0
+            } else {
+                x.signum() * x.abs().powf(exponent)
+            }
+        };
+        // U goes around the Y axis and V from the top to the bottom, like the cylinder
+        let angles = |u: f32, v: f32| ((TAU * u).sin_cos(), (PI * (0.5 - v)).sin_cos());
+        Self::new(move |u, v| {
+            let ((sin_theta, cos_theta), (sin_phi, cos_phi)) = angles(u, v);
+            let horizontal = power(cos_phi, exponents.y);
+            radius
+                * Vec3::new(
+                    horizontal * power(cos_theta, exponents.x),
+                    power(sin_phi, exponents.y),
+                    horizontal * power(sin_theta, exponents.x),
+                )
+        })
+        .with_normal(move |u, v| {
+            let ((sin_theta, cos_theta), (sin_phi, cos_phi)) = angles(u, v);
+            let horizontal = power(cos_phi, 2.0 - exponents.y);
+            Vec3::new(
+                horizontal * power(cos_theta, 2.0 - exponents.x),
+                power(sin_phi, 2.0 - exponents.y),
+                horizontal * power(sin_theta, 2.0 - exponents.x),
+            )
+        })
+        .with_wrapping(true, false)
+    }
+
+    /// A Möbius strip around the Y axis, `width` is across the strip
+    pub fn mobius_strip(radius: f32, width: f32) -> Self {
+        Self::new(move |u, v| {
+            let (sin_theta, cos_theta) = (TAU * u).sin_cos();
+            let (sin_half, cos_half) = (PI * u).sin_cos();
+            let offset = width * (v - 0.5);
+            let distance = radius + offset * cos_half;
+            Vec3::new(
+                distance * cos_theta,
+                offset * sin_half,
+                distance * sin_theta,
+            )
+        })
+        .with_resolution(64, 8)
+    }
+
+    /// The figure 8 immersion of a Klein bottle around the Y axis, `radius` is the distance from
+    /// the axis to the center of the tube, which needs to be above 2 so it doesn't cross the axis
+    pub fn klein_bottle(radius: f32) -> Self {
+        // The tube turns inside out once around U so only V wraps
+        Self::new(move |u, v| {
+            let (sin_theta, cos_theta) = (TAU * u).sin_cos();
+            let (sin_half, cos_half) = (PI * u).sin_cos();
+            let (sin_phi, sin_double_phi) = ((TAU * v).sin(), (2.0 * TAU * v).sin());
+            let distance = radius + cos_half * sin_phi - sin_half * sin_double_phi;
+            Vec3::new(
+                distance * cos_theta,
+                sin_half * sin_phi + cos_half * sin_double_phi,
+                distance * sin_theta,
+            )
+        })
+        .with_resolution(64, 32)
+        .with_wrapping(false, true)
+    }
+
+    /// Derivative of the position along U or V, a central difference clamped to `[0, 1]`
+    /// unless the surface wraps in that direction
+    fn derivative(&self, uv: Vec2, axis: Vec2, wrap: bool) -> Vec3 {
+        let t = uv.dot(axis);
+        let (before, after) = if wrap {
+            (t - NORMAL_EPSILON, t + NORMAL_EPSILON)
+        } else {
+            ((t - NORMAL_EPSILON).max(0.0), (t + NORMAL_EPSILON).min(1.0))
+        };
+        let at = |t: f32| {
+            // Only outside of [0, 1] when wrapping, where the surface repeats itself
+            let t = if wrap { t.rem_euclid(1.0) } else { t };
+            let uv = uv + axis * (t - uv.dot(axis));
+            (self.position)(uv.x, uv.y)
+        };
+        (at(after) - at(before)) / (after - before)
+    }
+
+    fn finite_difference_normal(&self, uv: Vec2) -> Vec3 {
+        let derivatives = |uv: Vec2| {
+            (
+                self.derivative(uv, Vec2::X, self.wrap.0),
+                self.derivative(uv, Vec2::Y, self.wrap.1),
+            )
+        };
+        let (mut du, mut dv) = derivatives(uv);
+        // At a pole the derivative along one direction is zero, apart from rounding errors.
+        // Use the normal slightly toward the middle of the surface instead.
+        if du.length() < 1e-4 * dv.length() || dv.length() < 1e-4 * du.length() {
+            (du, dv) = derivatives(uv.lerp(Vec2::splat(0.5), 1e-2));
+        }
+        du.cross(dv).normalize_or_zero()
+    }
+
+    /// Adds the vertices and triangles of the surface, row by row along V
+    fn build(&self, buffers: &mut MeshBuffers) {
+        let (columns, rows) = self.resolution;
+        assert!(columns > 0 && rows > 0);
+        let first = buffers.positions.len() as u32;
+        for i in 0..=rows {
+            let v = i as f32 / rows as f32;
+            for j in 0..=columns {
+                let u = j as f32 / columns as f32;
+                // The seam vertices are copies of the first column or row
+                let source = if self.wrap.0 && j == columns {
+                    Some(first + i * (columns + 1))
+                } else if self.wrap.1 && i == rows {
+                    Some(first + j)
+                } else {
+                    None
+                };
+                if let Some(source) = source {
+                    buffers.seam_vertex(source, [u, v]);
+                    continue;
+                }
+
+                let position = (self.position)(u, v);
+                let normal = match &self.normal {
+                    Some(normal) => normal(u, v).normalize_or_zero(),
+                    None => self.finite_difference_normal(Vec2::new(u, v)),
+                };
+                buffers.vertex(position, normal, [u, v]);
+            }
+        }
+        buffers.grid(first, columns, rows);
+    }
+}
+
+impl From<ParametricSurface<'_>> for Mesh {
+    fn from(surface: ParametricSurface) -> Self {
+        let mut buffers = MeshBuffers::default();
+        surface.build(&mut buffers);
+        buffers.into()
+    }
+}
+
+/// Vertices and triangles of a shape being built
+#[derive(Default)]
+struct MeshBuffers {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuffers {
+    /// Adds a vertex and returns its index
+    fn vertex(&mut self, position: Vec3, normal: Vec3, uv: [f32; 2]) -> u32 {
+        self.positions.push(position.into());
+        self.normals.push(normal.into());
+        self.uvs.push(uv);
+        self.positions.len() as u32 - 1
+    }
+
+    /// Adds a copy of the vertex `source` with another UV, so a seam has no crack
+    fn seam_vertex(&mut self, source: u32, uv: [f32; 2]) -> u32 {
+        let source = source as usize;
+        let (position, normal) = (self.positions[source], self.normals[source]);
+        self.vertex(position.into(), normal.into(), uv)
+    }
+
+    /// Adds a triangle, unless two of its vertices are at the same position
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        let [a_pos, b_pos, c_pos] = [a, b, c].map(|i| Vec3::from(self.positions[i as usize]));
+        let distinct = |p: Vec3, q: Vec3| p.distance_squared(q) > 1e-12;
+        if distinct(a_pos, b_pos) && distinct(b_pos, c_pos) && distinct(c_pos, a_pos) {
+            self.indices.extend([a, b, c]);
+        }
+    }
+
+    /// Adds the triangles of a grid of `columns + 1` by `rows + 1` vertices added row by row
+    /// from `first`. Triangles face the side of `d(position)/d(column) x d(position)/d(row)`,
+    /// the ones collapsed at a pole are skipped.
+    fn grid(&mut self, first: u32, columns: u32, rows: u32) {
+        for row in 0..rows {
+            let base1 = first + row * (columns + 1);
+            let base2 = base1 + columns + 1;
+            for j in 0..columns {
+                self.triangle(base1 + j, base1 + j + 1, base2 + j);
+                self.triangle(base1 + j + 1, base2 + j + 1, base2 + j);
+            }
+        }
+    }
+
+    /// Adds a disc on the XZ plane facing up or down, with its own vertices and planar UVs
+    fn disc(&mut self, radius: f32, height: f32, resolution: u32, up: bool) {
+        let normal = if up { Vec3::Y } else { -Vec3::Y };
+        // Seen from the side the disc faces, +X is on the right
+        let v_sign = if up { 1.0 } else { -1.0 };
+        let center = self.vertex(Vec3::new(0.0, height, 0.0), normal, [0.5, 0.5]);
+        let step = TAU / resolution as f32;
+        for j in 0..resolution {
+            let (sin, cos) = (step * j as f32).sin_cos();
+            self.vertex(
+                Vec3::new(cos * radius, height, sin * radius),
+                normal,
+                [0.5 + cos * 0.5, 0.5 + sin * 0.5 * v_sign],
+            );
+        }
+        for j in 0..resolution {
+            let j1 = (j + 1) % resolution;
+            if up {
+                self.triangle(center + 1 + j1, center + 1 + j, center);
+            } else {
+                self.triangle(center + 1 + j, center + 1 + j1, center);
+            }
+        }
+    }
+}
+
+impl From<MeshBuffers> for Mesh {
+    fn from(buffers: MeshBuffers) -> Self {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_indices(Some(Indices::U32(buffers.indices)));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, buffers.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, buffers.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, buffers.uvs);
+        mesh
+    }
+}
+
+/// A torus lying on the XZ plane, around the Y axis
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Torus {
+    /// Distance from the center of the torus to the center of the tube
+    pub radius: f32,
+    /// Radius of the tube
+    pub ring_radius: f32,
+    /// Number of vertices around the Y axis
+    pub resolution: u32,
+    /// Number of vertices around the tube
+    pub ring_resolution: u32,
+}
+
+impl Default for Torus {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            ring_radius: 0.2,
+            resolution: 32,
+            ring_resolution: 16,
+        }
+    }
+}
+
+impl From<Torus> for Mesh {
+    fn from(mut t: Torus) -> Self {
+        t.radius = at_least("torus radius", t.radius, MIN_SIZE);
+        t.ring_radius = at_least("torus ring radius", t.ring_radius, MIN_SIZE);
+        t.resolution = at_least("torus resolution", t.resolution, MIN_RESOLUTION);
+        t.ring_resolution = at_least("torus ring resolution", t.ring_resolution, MIN_RESOLUTION);
+
+        // U goes around the Y axis and V around the tube, starting from the outside going down.
+        // The vertices of the first column and row are copied at the end for the seams.
+        let mut buffers = MeshBuffers::default();
+        let columns = t.resolution + 1;
+        for i in 0..=t.ring_resolution {
+            let v = i as f32 / t.ring_resolution as f32;
+            let (sin_phi, cos_phi) = (TAU * v).sin_cos();
+            for j in 0..=t.resolution {
+                let u = j as f32 / t.resolution as f32;
+                if j == t.resolution {
+                    buffers.seam_vertex(i * columns, [u, v]);
+                    continue;
+                }
+                if i == t.ring_resolution {
+                    buffers.seam_vertex(j, [u, v]);
+                    continue;
+                }
+                let (sin_theta, cos_theta) = (TAU * u).sin_cos();
+                let normal = Vec3::new(cos_theta * cos_phi, -sin_phi, sin_theta * cos_phi);
+                let center = Vec3::new(cos_theta, 0.0, sin_theta) * t.radius;
+                buffers.vertex(center + normal * t.ring_radius, normal, [u, v]);
+            }
+        }
+        buffers.grid(0, t.resolution, t.ring_resolution);
+        buffers.into()
+    }
+}
+
+/// A cone, or a frustum if its top radius isn't zero, which stands on the XZ plane
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Cone {
+    /// Radius of the base (X&Z axis)
+    pub radius: f32,
+    /// Radius of the top, zero for a pointed cone
+    pub top_radius: f32,
+    /// Height of the cone (Y axis)
+    pub height: f32,
+    /// Number of vertices around each horizontal slice of the cone
+    pub resolution: u32,
+    /// Number of vertical subdivisions
+    pub subdivisions: u32,
+}
+
+impl Default for Cone {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            top_radius: 0.0,
+            height: 1.0,
+            resolution: 20,
+            subdivisions: 4,
+        }
+    }
+}
+
+impl From<Cone> for Mesh {
+    fn from(mut c: Cone) -> Self {
+        c.radius = at_least("cone radius", c.radius, MIN_SIZE);
+        c.top_radius = at_least("cone top radius", c.top_radius, 0.0);
+        c.height = at_least("cone height", c.height, MIN_SIZE);
+        c.resolution = at_least("cone resolution", c.resolution, MIN_RESOLUTION);
+        c.subdivisions = at_least("cone subdivisions", c.subdivisions, 1);
+
+        // U goes around the side and V from the top to the base, with a seam column
+        let mut buffers = MeshBuffers::default();
+        for i in 0..=c.subdivisions {
+            let v = i as f32 / c.subdivisions as f32;
+            let radius = c.top_radius + (c.radius - c.top_radius) * v;
+            let height = c.height * (0.5 - v);
+            for j in 0..=c.resolution {
+                let u = j as f32 / c.resolution as f32;
+                if j == c.resolution {
+                    buffers.seam_vertex(i * (c.resolution + 1), [u, v]);
+                    continue;
+                }
+                let (sin, cos) = (TAU * u).sin_cos();
+                let normal =
+                    Vec3::new(cos * c.height, c.radius - c.top_radius, sin * c.height).normalize();
+                buffers.vertex(
+                    Vec3::new(cos * radius, height, sin * radius),
+                    normal,
+                    [u, v],
+                );
+            }
+        }
+        buffers.grid(0, c.resolution, c.subdivisions);
+
+        if c.top_radius > 0.0 {
+            buffers.disc(c.top_radius, c.height * 0.5, c.resolution, true);
+        }
+        buffers.disc(c.radius, -c.height * 0.5, c.resolution, false);
+        buffers.into()
+    }
+}
+
+/// A capsule which stands on the XZ plane, a cylinder closed by two hemispheres
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Capsule {
+    /// Radius of the cylinder and the hemispheres (X&Z axis)
+    pub radius: f32,
+    /// Height of the cylinder between the hemispheres (Y axis)
+    pub depth: f32,
+    /// Number of vertices around each horizontal slice of the capsule
+    pub resolution: u32,
+    /// Number of rings of each hemisphere
+    pub rings: u32,
+}
+
+impl Default for Capsule {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            depth: 1.0,
+            resolution: 20,
+            rings: 8,
+        }
+    }
+}
+
+impl From<Capsule> for Mesh {
+    fn from(mut c: Capsule) -> Self {
+        c.radius = at_least("capsule radius", c.radius, MIN_SIZE);
+        c.depth = at_least("capsule depth", c.depth, 0.0);
+        c.resolution = at_least("capsule resolution", c.resolution, MIN_RESOLUTION);
+        c.rings = at_least("capsule rings", c.rings, 1);
+
+        // U goes around the Y axis and V follows the profile from the top pole to the bottom
+        // one, proportionally to its length so the texture isn't stretched on the cylinder
+        let length = PI * c.radius + c.depth;
+        let mut buffers = MeshBuffers::default();
+        for (first_angle, center) in [(0.0, c.depth * 0.5), (FRAC_PI_2, -c.depth * 0.5)] {
+            for i in 0..=c.rings {
+                let phi = first_angle + FRAC_PI_2 * i as f32 / c.rings as f32;
+                let arc = c.radius * phi + if center < 0.0 { c.depth } else { 0.0 };
+                let (sin_phi, cos_phi) = phi.sin_cos();
+                let row = buffers.positions.len() as u32;
+                for j in 0..=c.resolution {
+                    let u = j as f32 / c.resolution as f32;
+                    if j == c.resolution {
+                        buffers.seam_vertex(row, [u, arc / length]);
+                        continue;
+                    }
+                    let (sin_theta, cos_theta) = (TAU * u).sin_cos();
+                    let normal = Vec3::new(sin_phi * cos_theta, cos_phi, sin_phi * sin_theta);
+                    let position = normal * c.radius + Vec3::new(0.0, center, 0.0);
+                    buffers.vertex(position, normal, [u, arc / length]);
+                }
+            }
+        }
+        buffers.grid(0, c.resolution, 2 * c.rings + 1);
+        buffers.into()
+    }
+}
+
+/// Vertices and triangles of an icosahedron inscribed in the unit sphere
+fn icosahedron() -> (Vec<Vec3>, Vec<[u32; 3]>) {
+    // Golden ratio
+    let t = (1.0 + 5f32.sqrt()) * 0.5;
+    let points = [
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ]
+    .iter()
+    .map(|&p| Vec3::from(p).normalize())
+    .collect();
+    let triangles = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+    (points, triangles)
+}
+
+/// A sphere made of subdivided icosahedron triangles, which are all about the same size
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Icosphere {
+    pub radius: f32,
+    /// Number of times the triangles of the icosahedron are split in four
+    pub subdivisions: u32,
+}
+
+impl Default for Icosphere {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            subdivisions: 3,
+        }
+    }
+}
+
+impl From<Icosphere> for Mesh {
+    fn from(mut sphere: Icosphere) -> Self {
+        sphere.radius = at_least("icosphere radius", sphere.radius, MIN_SIZE);
+        sphere.subdivisions = at_most(
+            "icosphere subdivisions",
+            sphere.subdivisions,
+            MAX_ICOSPHERE_SUBDIVISIONS,
+        );
+
+        let (mut points, mut triangles) = icosahedron();
+
+        for _ in 0..sphere.subdivisions {
+            let mut midpoints = HashMap::default();
+            let mut midpoint = |a: u32, b: u32| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    points.push((points[a as usize] + points[b as usize]).normalize());
+                    points.len() as u32 - 1
+                })
+            };
+            triangles = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let mut buffers = MeshBuffers::default();
+        for &point in &points {
+            let u = (point.z.atan2(point.x) / TAU).rem_euclid(1.0);
+            let v = point.y.clamp(-1.0, 1.0).acos() / PI;
+            buffers.vertex(point * sphere.radius, point, [u, v]);
+        }
+
+        // Triangles crossing the seam use copies of the vertices at the start of the texture
+        // moved to its end, and each triangle touching a pole gets its own pole vertex with
+        // the U of the middle of the triangle
+        let is_pole = |i: u32| {
+            // Seam copies are after the points and never at a pole
+            matches!(points.get(i as usize), Some(point) if point.x.abs() < 1e-6 && point.z.abs() < 1e-6)
+        };
+        let mut seam_copies = HashMap::default();
+        for triangle in &mut triangles {
+            let us: Vec<f32> = triangle
+                .iter()
+                .filter(|&&i| !is_pole(i))
+                .map(|&i| buffers.uvs[i as usize][0])
+                .collect();
+            let (min, max) = us
+                .iter()
+                .fold((1.0f32, 0.0f32), |(min, max), &u| (min.min(u), max.max(u)));
+            let crosses_seam = max - min > 0.5;
+            for i in triangle.iter_mut() {
+                let [u, v] = buffers.uvs[*i as usize];
+                if crosses_seam && !is_pole(*i) && u < 0.5 {
+                    let point = points[*i as usize];
+                    *i = *seam_copies.entry(*i).or_insert_with(|| {
+                        buffers.vertex(point * sphere.radius, point, [u + 1.0, v])
+                    });
+                }
+            }
+            if let Some(pole) = triangle.iter().position(|&i| is_pole(i)) {
+                let others = triangle.iter().filter(|&&i| !is_pole(i));
+                let u = others.map(|&i| buffers.uvs[i as usize][0]).sum::<f32>() / 2.0;
+                let point = points[triangle[pole] as usize];
+                let v = buffers.uvs[triangle[pole] as usize][1];
+                triangle[pole] = buffers.vertex(point * sphere.radius, point, [u, v]);
+            }
+            buffers.indices.extend(triangle.iter());
+        }
+        buffers.into()
+    }
+}
+
+/// A flat grid on the XZ plane facing up, subdivided in quads for vertex displacement
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Grid {
+    /// Size along the X axis
+    pub width: f32,
+    /// Size along the Z axis
+    pub depth: f32,
+    /// Number of quads along the X axis
+    pub subdivisions_x: u32,
+    /// Number of quads along the Z axis
+    pub subdivisions_z: u32,
+    /// Number of times the UVs go from 0 to 1 along X and Z
+    pub uv_tiling: Vec2,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            depth: 1.0,
+            subdivisions_x: 32,
+            subdivisions_z: 32,
+            uv_tiling: Vec2::ONE,
+        }
+    }
+}
+
+impl From<Grid> for Mesh {
+    fn from(mut g: Grid) -> Self {
+        g.width = at_least("grid width", g.width, MIN_SIZE);
+        g.depth = at_least("grid depth", g.depth, MIN_SIZE);
+        g.subdivisions_x = at_least("grid subdivisions along X", g.subdivisions_x, 1);
+        g.subdivisions_z = at_least("grid subdivisions along Z", g.subdivisions_z, 1);
+
+        // U goes along +X and V along +Z. The rows of the grid go along X so the triangles face up.
+        let mut buffers = MeshBuffers::default();
+        for i in 0..=g.subdivisions_x {
+            let x = i as f32 / g.subdivisions_x as f32;
+            for j in 0..=g.subdivisions_z {
+                let z = j as f32 / g.subdivisions_z as f32;
+                buffers.vertex(
+                    Vec3::new((x - 0.5) * g.width, 0.0, (z - 0.5) * g.depth),
+                    Vec3::Y,
+                    [x * g.uv_tiling.x, z * g.uv_tiling.y],
+                );
+            }
+        }
+        buffers.grid(0, g.subdivisions_z, g.subdivisions_x);
+        buffers.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{render::mesh::VertexAttributeValues, utils::HashSet};
+
+    use super::*;
+
+    /// Attributes of a generated mesh
+    struct Shape {
+        name: &'static str,
+        positions: Vec<[f32; 3]>,
+        normals: Vec<[f32; 3]>,
+        uvs: Vec<[f32; 2]>,
+        indices: Vec<u32>,
+    }
+
+    impl Shape {
+        fn new(name: &'static str, mesh: &Mesh) -> Self {
+            assert_eq!(mesh.primitive_topology(), PrimitiveTopology::TriangleList);
+            let attribute = |attribute| match mesh.attribute(attribute) {
+                Some(VertexAttributeValues::Float32x3(values)) => values.clone(),
+                _ => panic!("{}: missing attribute", name),
+            };
+            let positions = attribute(Mesh::ATTRIBUTE_POSITION);
+            let normals = attribute(Mesh::ATTRIBUTE_NORMAL);
+            let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+                Some(VertexAttributeValues::Float32x2(uvs)) => uvs.clone(),
+                _ => panic!("{}: missing UVs", name),
+            };
+            let indices = match mesh.indices() {
+                Some(Indices::U32(indices)) => indices.clone(),
+                _ => panic!("{}: missing u32 indices", name),
+            };
+            Self {
+                name,
+                positions,
+                normals,
+                uvs,
+                indices,
+            }
+        }
+
+        fn assert_counts(&self, vertices: usize, triangles: usize) {
+            assert_eq!(self.positions.len(), vertices, "{}: vertices", self.name);
+            assert_eq!(self.normals.len(), vertices, "{}: normals", self.name);
+            assert_eq!(self.uvs.len(), vertices, "{}: UVs", self.name);
+            assert_eq!(self.indices.len(), triangles * 3, "{}: indices", self.name);
+        }
+
+        fn assert_indices_in_bounds(&self) {
+            assert_eq!(self.indices.len() % 3, 0, "{}: partial triangle", self.name);
+            for &i in &self.indices {
+                assert!(
+                    (i as usize) < self.positions.len(),
+                    "{}: index {} is out of bounds",
+                    self.name,
+                    i
+                );
+            }
+        }
+
+        fn assert_unit_normals(&self) {
+            for (i, &normal) in self.normals.iter().enumerate() {
+                let length = Vec3::from(normal).length();
+                assert!(
+                    (length - 1.0).abs() < 1e-4,
+                    "{}: normal {} has a length of {}",
+                    self.name,
+                    i,
+                    length
+                );
+            }
+        }
+
+        /// Every triangle faces the same side as the normals of its vertices
+        fn assert_outward_winding(&self) {
+            for (t, triangle) in self.indices.chunks_exact(3).enumerate() {
+                let [a, b, c] = [0, 1, 2].map(|k| Vec3::from(self.positions[triangle[k] as usize]));
+                let face_normal = (b - a).cross(c - a);
+                assert!(
+                    face_normal.length() > 0.0,
+                    "{}: triangle {} is degenerate",
+                    self.name,
+                    t
+                );
+                for &i in triangle {
+                    let normal = Vec3::from(self.normals[i as usize]);
+                    assert!(
+                        face_normal.dot(normal) > 0.0,
+                        "{}: triangle {} faces away from the normal of vertex {}",
+                        self.name,
+                        t,
+                        i
+                    );
+                }
+            }
+        }
+
+        /// Every UV is in `[0, 1]` apart from U which may go up to `max_u` past the seam
+        fn assert_uv_range(&self, max_u: f32) {
+            for (i, &[u, v]) in self.uvs.iter().enumerate() {
+                assert!(
+                    (0.0..=max_u).contains(&u) && (0.0..=1.0).contains(&v),
+                    "{}: UV {} is out of range: {}, {}",
+                    self.name,
+                    i,
+                    u,
+                    v
+                );
+            }
+        }
+
+        /// Once the vertices at the same position are merged, every edge is shared by exactly two
+        /// triangles which use it in opposite directions
+        fn assert_watertight(&self) {
+            let mut merged = HashMap::default();
+            let welded: Vec<usize> = self
+                .positions
+                .iter()
+                .map(|&p| {
+                    let key = p.map(|x| (x * 1e4).round() as i64);
+                    let next = merged.len();
+                    *merged.entry(key).or_insert(next)
+                })
+                .collect();
+
+            let mut edges = HashSet::default();
+            for triangle in self.indices.chunks_exact(3) {
+                for k in 0..3 {
+                    let edge = (
+                        welded[triangle[k] as usize],
+                        welded[triangle[(k + 1) % 3] as usize],
+                    );
+                    assert!(
+                        edges.insert(edge),
+                        "{}: edge {:?} is used twice in the same direction",
+                        self.name,
+                        edge
+                    );
+                }
+            }
+            for &(a, b) in &edges {
+                assert!(
+                    edges.contains(&(b, a)),
+                    "{}: edge {:?} is on a hole",
+                    self.name,
+                    (a, b)
+                );
+            }
+        }
+
+        fn assert_valid(&self, max_u: f32) {
+            self.assert_indices_in_bounds();
+            self.assert_unit_normals();
+            self.assert_outward_winding();
+            self.assert_uv_range(max_u);
+            self.assert_watertight();
+        }
+    }
+
+    #[test]
+    fn cylinder() {
+        let cylinder = Cylinder {
+            resolution: 12,
+            subdivisions: 3,
+            ..Cylinder::default()
+        };
+        let shape = Shape::new("cylinder", &Mesh::from(cylinder));
+        // Shaft with a seam column, then two caps with a center vertex
+        shape.assert_counts(13 * 4 + 2 * 13, 2 * 12 * 3 + 2 * 12);
+        shape.assert_valid(1.0);
+    }
+
+    #[test]
+    fn parametric_sphere() {
+        let sphere = ParametricSurface::superellipsoid(0.5, Vec2::ONE).with_resolution(12, 6);
+        let shape = Shape::new("parametric sphere", &Mesh::from(sphere));
+        // The triangles collapsed at the poles are skipped
+        shape.assert_counts(13 * 7, 2 * 12 * 6 - 2 * 12);
+        shape.assert_valid(1.0);
+        for (i, (&position, &normal)) in shape.positions.iter().zip(&shape.normals).enumerate() {
+            assert!(
+                (Vec3::from(position) * 2.0).distance(Vec3::from(normal)) < 1e-4,
+                "normal {} isn't radial",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn finite_difference_normals() {
+        let sphere = |u: f32, v: f32| {
+            let (sin_theta, cos_theta) = (TAU * u).sin_cos();
+            let (sin_phi, cos_phi) = (PI * (0.5 - v)).sin_cos();
+            Vec3::new(cos_phi * cos_theta, sin_phi, cos_phi * sin_theta)
+        };
+        let surface = ParametricSurface::new(sphere)
+            .with_resolution(12, 6)
+            .with_wrapping(true, false);
+        let shape = Shape::new("finite differences sphere", &Mesh::from(surface));
+        shape.assert_valid(1.0);
+        for (i, (&position, &normal)) in shape.positions.iter().zip(&shape.normals).enumerate() {
+            // The poles use the normal of a point next to them
+            assert!(
+                Vec3::from(position).distance(Vec3::from(normal)) < 0.05,
+                "normal {} isn't radial: {:?}",
+                i,
+                normal
+            );
+        }
+    }
+
+    #[test]
+    fn superellipsoid() {
+        let cube = ParametricSurface::superellipsoid(0.5, Vec2::splat(0.2));
+        let shape = Shape::new("superellipsoid", &Mesh::from(cube));
+        shape.assert_indices_in_bounds();
+        shape.assert_unit_normals();
+        shape.assert_uv_range(1.0);
+        shape.assert_watertight();
+    }
+
+    #[test]
+    fn wrapped_seam_is_exact() {
+        let surface = ParametricSurface::superellipsoid(0.5, Vec2::ONE).with_resolution(12, 6);
+        let shape = Shape::new("superellipsoid", &Mesh::from(surface));
+        for row in 0..=6 {
+            let first = row * 13;
+            assert_eq!(
+                Vec3::from(shape.positions[first]),
+                Vec3::from(shape.positions[first + 12])
+            );
+            assert_eq!(
+                Vec3::from(shape.normals[first]),
+                Vec3::from(shape.normals[first + 12])
+            );
+            assert_eq!(
+                Vec2::from(shape.uvs[first + 12]),
+                Vec2::new(1.0, row as f32 / 6.0)
+            );
+        }
+    }
+
+    #[test]
+    fn non_orientable_surfaces() {
+        for (name, surface) in [
+            ("mobius strip", ParametricSurface::mobius_strip(1.0, 0.5)),
+            ("klein bottle", ParametricSurface::klein_bottle(3.0)),
+        ] {
+            let shape = Shape::new(name, &Mesh::from(surface));
+            shape.assert_indices_in_bounds();
+            shape.assert_unit_normals();
+            shape.assert_uv_range(1.0);
+        }
+    }
+
+    #[test]
+    fn torus() {
+        let torus = Torus {
+            resolution: 16,
+            ring_resolution: 8,
+            ..Torus::default()
+        };
+        let shape = Shape::new("torus", &Mesh::from(torus));
+        shape.assert_counts(17 * 9, 2 * 16 * 8);
+        shape.assert_valid(1.0);
+    }
+
+    #[test]
+    fn torus_seams_are_exact() {
+        let torus = Torus {
+            resolution: 16,
+            ring_resolution: 8,
+            ..Torus::default()
+        };
+        let shape = Shape::new("torus", &Mesh::from(torus));
+        let copies = (0..=8)
+            .map(|row| (row * 17, row * 17 + 16))
+            .chain((0..=16).map(|column| (column, 8 * 17 + column)));
+        for (first, copy) in copies {
+            assert_eq!(
+                Vec3::from(shape.positions[first]),
+                Vec3::from(shape.positions[copy])
+            );
+            assert_eq!(
+                Vec3::from(shape.normals[first]),
+                Vec3::from(shape.normals[copy])
+            );
+        }
+    }
+
+    #[test]
+    fn cone() {
+        let cone = Cone {
+            resolution: 12,
+            subdivisions: 3,
+            ..Cone::default()
+        };
+        let shape = Shape::new("cone", &Mesh::from(cone));
+        // The triangles collapsed at the tip are skipped, there is no top cap
+        shape.assert_counts(13 * 4 + 13, 2 * 12 * 3 - 12 + 12);
+        shape.assert_valid(1.0);
+    }
+
+    #[test]
+    fn frustum() {
+        let frustum = Cone {
+            top_radius: 0.25,
+            resolution: 12,
+            subdivisions: 3,
+            ..Cone::default()
+        };
+        let shape = Shape::new("frustum", &Mesh::from(frustum));
+        shape.assert_counts(13 * 4 + 2 * 13, 2 * 12 * 3 + 2 * 12);
+        shape.assert_valid(1.0);
+    }
+
+    #[test]
+    fn capsule() {
+        let capsule = Capsule {
+            resolution: 12,
+            rings: 4,
+            ..Capsule::default()
+        };
+        let shape = Shape::new("capsule", &Mesh::from(capsule));
+        // 2 hemispheres of 5 rows, joined by the cylinder, without the triangles at the poles
+        shape.assert_counts(2 * 5 * 13, 2 * 12 * 9 - 2 * 12);
+        shape.assert_valid(1.0);
+    }
+
+    #[test]
+    fn capsule_without_cylinder() {
+        let sphere = Capsule {
+            depth: 0.0,
+            resolution: 12,
+            rings: 4,
+            ..Capsule::default()
+        };
+        let shape = Shape::new("capsule without cylinder", &Mesh::from(sphere));
+        shape.assert_counts(2 * 5 * 13, 2 * 12 * 8 - 2 * 12);
+        shape.assert_valid(1.0);
+    }
+
+    #[test]
+    fn icosphere() {
+        for subdivisions in 0..4 {
+            let icosphere = Icosphere {
+                subdivisions,
+                ..Icosphere::default()
+            };
+            let shape = Shape::new("icosphere", &Mesh::from(icosphere));
+            let triangles = 20 * 4usize.pow(subdivisions);
+            assert_eq!(shape.indices.len(), triangles * 3);
+            assert!(shape.positions.len() >= triangles / 2 + 2);
+            // The triangles crossing the seam have U past 1, by less than their width
+            shape.assert_valid(1.5);
+        }
+    }
+
+    #[test]
+    fn invalid_settings_are_clamped() {
+        let meshes = [
+            (
+                "cylinder",
+                Mesh::from(Cylinder {
+                    radius: 0.0,
+                    height: -1.0,
+                    resolution: 2,
+                    subdivisions: 0,
+                }),
+            ),
+            (
+                "torus",
+                Mesh::from(Torus {
+                    radius: f32::NAN,
+                    ring_radius: 0.0,
+                    resolution: 2,
+                    ring_resolution: 0,
+                }),
+            ),
+            (
+                "cone",
+                Mesh::from(Cone {
+                    radius: 0.0,
+                    top_radius: -1.0,
+                    height: 0.0,
+                    resolution: 1,
+                    subdivisions: 0,
+                }),
+            ),
+            (
+                "capsule",
+                Mesh::from(Capsule {
+                    radius: -1.0,
+                    depth: -1.0,
+                    resolution: 0,
+                    rings: 0,
+                }),
+            ),
+            (
+                "icosphere",
+                Mesh::from(Icosphere {
+                    radius: 0.0,
+                    subdivisions: 8,
+                }),
+            ),
+            (
+                "grid",
+                Mesh::from(Grid {
+                    width: 0.0,
+                    depth: -1.0,
+                    subdivisions_x: 0,
+                    subdivisions_z: 0,
+                    uv_tiling: Vec2::ONE,
+                }),
+            ),
+        ];
+        for (name, mesh) in meshes {
+            let shape = Shape::new(name, &mesh);
+            assert!(!shape.indices.is_empty(), "{}: no triangles", name);
+            shape.assert_indices_in_bounds();
+        }
+    }
+
+    #[test]
+    fn grid() {
+        let grid = Grid {
+            width: 2.0,
+            depth: 3.0,
+            subdivisions_x: 4,
+            subdivisions_z: 6,
+            uv_tiling: Vec2::new(2.0, 1.0),
+        };
+        let shape = Shape::new("grid", &Mesh::from(grid));
+        shape.assert_counts(5 * 7, 2 * 4 * 6);
+        shape.assert_indices_in_bounds();
+        shape.assert_unit_normals();
+        shape.assert_outward_winding();
+        shape.assert_uv_range(2.0);
+        // Opposite corners
+        let last = 5 * 7 - 1;
+        assert_eq!(Vec3::from(shape.positions[0]), Vec3::new(-1.0, 0.0, -1.5));
+        assert_eq!(Vec2::from(shape.uvs[0]), Vec2::ZERO);
+        assert_eq!(Vec3::from(shape.positions[last]), Vec3::new(1.0, 0.0, 1.5));
+        assert_eq!(Vec2::from(shape.uvs[last]), Vec2::new(2.0, 1.0));
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::bail;
+use bevy::{
+    math::{Vec2, Vec3},
+    prelude::Mesh,
+    render::{
+        mesh::{Indices, MeshVertexAttribute, VertexAttributeValues},
+        render_resource::PrimitiveTopology,
+    },
+};
+
+fn attribute<'a, T>(
+    mesh: &'a Mesh,
+    attribute: MeshVertexAttribute,
+    values: impl Fn(&'a VertexAttributeValues) -> Option<&'a Vec<T>>,
+) -> anyhow::Result<&'a Vec<T>> {
+    let name = attribute.name;
+    match mesh.attribute(attribute).and_then(values) {
+        Some(values) => Ok(values),
+        None => bail!("the mesh has no {} attribute of the expected format", name),
+    }
+}
+
+/// Computes `Mesh::ATTRIBUTE_TANGENT` for an indexed triangle list with normals and UVs.
+///
+/// Like mikktspace, the tangent of each triangle points toward increasing U. They are projected
+/// on the plane of the vertex normal and summed around each vertex, weighted by the angle of the
+/// triangle at that vertex. W holds the handedness: `bitangent = cross(normal, tangent.xyz) * w`.
+pub fn generate_tangents(mesh: &mut Mesh) -> anyhow::Result<()> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        bail!("tangents can only be generated for triangle lists");
+    }
+    let indices: Vec<usize> = match mesh.indices() {
+        Some(Indices::U16(indices)) => indices.iter().map(|&i| i as usize).collect(),
+        Some(Indices::U32(indices)) => indices.iter().map(|&i| i as usize).collect(),
+        None => bail!("tangents can only be generated for indexed meshes"),
+    };
+    let positions = attribute(mesh, Mesh::ATTRIBUTE_POSITION, |values| match values {
+        VertexAttributeValues::Float32x3(values) => Some(values),
+        _ => None,
+    })?;
+    let normals = attribute(mesh, Mesh::ATTRIBUTE_NORMAL, |values| match values {
+        VertexAttributeValues::Float32x3(values) => Some(values),
+        _ => None,
+    })?;
+    let uvs = attribute(mesh, Mesh::ATTRIBUTE_UV_0, |values| match values {
+        VertexAttributeValues::Float32x2(values) => Some(values),
+        _ => None,
+    })?;
+    if let Some(&index) = indices.iter().find(|&&i| i >= positions.len()) {
+        bail!("index {} is out of bounds", index);
+    }
+
+    let normal = |i: usize| Vec3::from(normals[i]).normalize_or_zero();
+    let mut tangents = vec![Vec3::ZERO; positions.len()];
+    let mut bitangents = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [p0, p1, p2] = [0, 1, 2].map(|k| Vec3::from(positions[triangle[k]]));
+        let [uv0, uv1, uv2] = [0, 1, 2].map(|k| Vec2::from(uvs[triangle[k]]));
+        let (edge1, edge2) = (p1 - p0, p2 - p0);
+        let (duv1, duv2) = (uv1 - uv0, uv2 - uv0);
+        let determinant = duv1.x * duv2.y - duv2.x * duv1.y;
+        // The UVs of the triangle are degenerate, it doesn't tell anything about the tangents
+        if determinant.abs() < f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge1 * duv2.y - edge2 * duv1.y) / determinant;
+        let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / determinant;
+
+        for k in 0..3 {
+            let vertex = triangle[k];
+            let position = Vec3::from(positions[vertex]);
+            let to_next =
+                (Vec3::from(positions[triangle[(k + 1) % 3]]) - position).normalize_or_zero();
+            let to_previous =
+                (Vec3::from(positions[triangle[(k + 2) % 3]]) - position).normalize_or_zero();
+            let angle = to_next.dot(to_previous).clamp(-1.0, 1.0).acos();
+
+            let n = normal(vertex);
+            tangents[vertex] += (tangent - n * n.dot(tangent)).normalize_or_zero() * angle;
+            bitangents[vertex] += (bitangent - n * n.dot(bitangent)).normalize_or_zero() * angle;
+        }
+    }
+
+    let tangents: Vec<[f32; 4]> = tangents
+        .iter()
+        .zip(&bitangents)
+        .enumerate()
+        .map(|(vertex, (&tangent, &bitangent))| {
+            let n = normal(vertex);
+            let mut tangent = (tangent - n * n.dot(tangent)).normalize_or_zero();
+            // No triangle with usable UVs around the vertex, any direction will do
+            if tangent == Vec3::ZERO {
+                tangent = n.any_orthonormal_vector();
+            }
+            let w = if n.cross(tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            tangent.extend(w).into()
+        })
+        .collect();
+    mesh.insert_attribute(Mesh::ATTRIBUTE_TANGENT, tangents);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::{Capsule, Cone, Cylinder, Grid, Icosphere, Torus};
+
+    fn assert_tangents_orthogonal(name: &str, mut mesh: Mesh) {
+        generate_tangents(&mut mesh).unwrap();
+        let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            Some(VertexAttributeValues::Float32x3(normals)) => normals,
+            _ => panic!("{} has no normals", name),
+        };
+        let tangents = match mesh.attribute(Mesh::ATTRIBUTE_TANGENT) {
+            Some(VertexAttributeValues::Float32x4(tangents)) => tangents,
+            _ => panic!("{} has no tangents", name),
+        };
+        assert_eq!(normals.len(), tangents.len());
+
+        for (i, (&normal, &[x, y, z, w])) in normals.iter().zip(tangents).enumerate() {
+            let normal = Vec3::from(normal);
+            let tangent = Vec3::new(x, y, z);
+            assert!(
+                (tangent.length() - 1.0).abs() < 1e-4,
+                "{}: tangent {} isn't normalized: {}",
+                name,
+                i,
+                tangent
+            );
+            assert!(
+                normal.dot(tangent).abs() < 1e-4,
+                "{}: tangent {} isn't orthogonal to the normal: {} . {}",
+                name,
+                i,
+                tangent,
+                normal
+            );
+            assert!(
+                (w.abs() - 1.0).abs() < f32::EPSILON,
+                "{}: handedness {} is {}",
+                name,
+                i,
+                w
+            );
+        }
+    }
+
+    #[test]
+    fn cylinder_tangents_are_orthogonal() {
+        assert_tangents_orthogonal("cylinder", Cylinder::default().into());
+    }
+
+    #[test]
+    fn torus_tangents_are_orthogonal() {
+        assert_tangents_orthogonal("torus", Torus::default().into());
+    }
+
+    #[test]
+    fn cone_tangents_are_orthogonal() {
+        assert_tangents_orthogonal("cone", Cone::default().into());
+        let frustum = Cone {
+            top_radius: 0.25,
+            ..Cone::default()
+        };
+        assert_tangents_orthogonal("frustum", frustum.into());
+    }
+
+    #[test]
+    fn capsule_tangents_are_orthogonal() {
+        assert_tangents_orthogonal("capsule", Capsule::default().into());
+    }
+
+    #[test]
+    fn icosphere_tangents_are_orthogonal() {
+        assert_tangents_orthogonal("icosphere", Icosphere::default().into());
+    }
+
+    #[test]
+    fn grid_tangents_are_orthogonal() {
+        assert_tangents_orthogonal("grid", Grid::default().into());
+    }
+
+    #[test]
+    fn tangents_follow_u() {
+        let mut mesh = Mesh::from(Cylinder::default());
+        generate_tangents(&mut mesh).unwrap();
+        let tangents = match mesh.attribute(Mesh::ATTRIBUTE_TANGENT) {
+            Some(VertexAttributeValues::Float32x4(tangents)) => tangents,
+            _ => unreachable!(),
+        };
+        // The first vertex of the shaft is at +X, U increases toward +Z
+        let [x, y, z, _] = tangents[0];
+        assert!(
+            Vec3::new(x, y, z).distance(Vec3::Z) < 1e-4,
+            "{:?}",
+            tangents[0]
+        );
+    }
+
+    #[test]
+    fn meshes_without_uvs_are_rejected() {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0.0f32; 3]; 3]);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0f32, 1.0, 0.0]; 3]);
+        mesh.set_indices(Some(Indices::U32(vec![0, 1, 2])));
+        assert!(generate_tangents(&mut mesh).is_err());
+    }
+}